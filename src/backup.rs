@@ -0,0 +1,402 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::formats::{Format, FormatOptions};
+use crate::user_dictionary::ChecksumStatus;
+
+/// Each backup run directory has one of these describing where the files came from
+const MANIFEST_FILE_NAME: &str = "manifest.toml";
+
+/// A single file that was copied into a backup run
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackupFile {
+    /// Name of the dictionary in the config file
+    pub name: String,
+    /// Where the file was copied from and where it will be restored to
+    pub path: PathBuf,
+    /// File name of the copy, relative to the run directory
+    pub backup: String,
+    /// Format the file was read in; runs from older versions are all Chromium
+    #[serde(default)]
+    pub format: Format,
+}
+
+/// Describes everything that was backed up during a single run
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    pub run: String,
+    pub created: String,
+    pub files: Vec<BackupFile>,
+}
+
+/// Copies the prior contents of files into a new, timestamped directory before they are overwritten
+#[derive(Debug)]
+pub struct BackupRun {
+    dir: PathBuf,
+    manifest: Manifest,
+}
+
+impl BackupRun {
+    /// Creates a new run directory inside `backup_dir`. Run names sort oldest -> newest.
+    pub fn new(backup_dir: &Path) -> Result<Self, io::Error> {
+        let now = Utc::now();
+        let base_run = now.format("%Y%m%dT%H%M%S%3fZ").to_string();
+
+        // Two runs in the same millisecond is unlikely but not impossible
+        let mut run = base_run.clone();
+        let mut attempt = 0;
+        while backup_dir.join(&run).exists() {
+            attempt += 1;
+            run = format!("{}-{}", base_run, attempt);
+        }
+
+        let dir = backup_dir.join(&run);
+        fs::create_dir_all(&dir)?;
+        debug!("Created backup run directory '{}'", dir.display());
+
+        Ok(BackupRun {
+            dir,
+            manifest: Manifest {
+                run,
+                created: now.to_rfc3339(),
+                files: Vec::new(),
+            },
+        })
+    }
+
+    /// Copies `path` into the run. Files that don't exist yet have nothing to back up and are skipped.
+    pub fn snapshot(&mut self, name: &str, path: &Path, format: Format) -> Result<(), io::Error> {
+        if !path.exists() {
+            debug!(
+                "Nothing to back up for '{}'; '{}' does not exist",
+                name,
+                path.display()
+            );
+            return Ok(());
+        }
+
+        // Same extension as the original so the copy opens in whatever handles that kind of file
+        let backup = match path.extension() {
+            Some(extension) => format!(
+                "{}.{}",
+                sanitize_file_name(name),
+                sanitize_file_name(&extension.to_string_lossy())
+            ),
+            None => sanitize_file_name(name),
+        };
+        fs::copy(path, self.dir.join(&backup))?;
+        debug!("Backed up '{}' to '{}'", path.display(), backup);
+
+        self.manifest.files.push(BackupFile {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            backup,
+            format,
+        });
+        Ok(())
+    }
+
+    /// Writes the manifest so the run can be listed and restored later
+    pub fn finish(self) -> Result<Manifest, io::Error> {
+        let manifest = toml::to_string(&self.manifest)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(self.dir.join(MANIFEST_FILE_NAME), manifest)?;
+        info!(
+            "Backed up '{}' files to '{}'",
+            self.manifest.files.len(),
+            self.dir.display()
+        );
+        Ok(self.manifest)
+    }
+}
+
+/// Returns the manifest of every backup run in `backup_dir`, oldest first
+pub fn list_runs(backup_dir: &Path) -> Result<Vec<Manifest>, io::Error> {
+    if !backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut runs = Vec::new();
+    for entry in fs::read_dir(backup_dir)? {
+        let manifest_path = entry?.path().join(MANIFEST_FILE_NAME);
+        // Directories without a manifest are from runs that did not finish; not something we can restore
+        if !manifest_path.exists() {
+            continue;
+        }
+        let content = fs::read_to_string(&manifest_path)?;
+        let manifest: Manifest = toml::from_str(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        runs.push(manifest);
+    }
+    runs.sort_by(|a, b| a.run.cmp(&b.run));
+    Ok(runs)
+}
+
+/// Removes the oldest runs so that at most `keep` remain. A `keep` of 0 keeps everything.
+/// Returns the names of the runs that were removed.
+pub fn prune(backup_dir: &Path, keep: usize) -> Result<Vec<String>, io::Error> {
+    let runs = list_runs(backup_dir)?;
+    if keep == 0 || runs.len() <= keep {
+        return Ok(Vec::new());
+    }
+
+    let mut removed = Vec::new();
+    for manifest in &runs[..runs.len() - keep] {
+        fs::remove_dir_all(backup_dir.join(&manifest.run))?;
+        debug!("Removed old backup run '{}'", manifest.run);
+        removed.push(manifest.run.clone());
+    }
+    Ok(removed)
+}
+
+/// The files `restore()` would put back, without touching anything.
+/// If `name` is given, only that dictionary's file is returned.
+pub fn files_to_restore(
+    backup_dir: &Path,
+    run: &str,
+    name: Option<&str>,
+) -> Result<Vec<BackupFile>, Error> {
+    let manifest = list_runs(backup_dir)?
+        .into_iter()
+        .find(|manifest| manifest.run == run)
+        .ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No backup run named '{}'", run),
+        ))?;
+
+    let files: Vec<BackupFile> = manifest
+        .files
+        .into_iter()
+        .filter(|file| name.is_none_or(|name| file.name == name))
+        .collect();
+    if files.is_empty() {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Backup run '{}' has no copy of dictionary '{}'",
+                run,
+                name.unwrap_or_default()
+            ),
+        )));
+    }
+    Ok(files)
+}
+
+/// Puts the files from backup `run` back where they came from.
+/// If `name` is given, only that dictionary is restored.
+/// The files being replaced are backed up as a new run first and old runs are pruned down to `keep` after.
+pub fn restore(
+    backup_dir: &Path,
+    run: &str,
+    name: Option<&str>,
+    keep: usize,
+) -> Result<Vec<BackupFile>, Error> {
+    let files = files_to_restore(backup_dir, run, name)?;
+
+    // Restoring is just another way of overwriting dictionaries; make sure it can be undone too
+    let mut undo_run = BackupRun::new(backup_dir)?;
+    for file in &files {
+        undo_run.snapshot(&file.name, &file.path, file.format)?;
+    }
+    undo_run.finish()?;
+
+    let run_dir = backup_dir.join(run);
+    for file in &files {
+        let bytes = restored_bytes(&fs::read(run_dir.join(&file.backup))?, file)?;
+        fs::write(&file.path, bytes).map_err(|source| Error::Write {
+            path: file.path.clone(),
+            source,
+        })?;
+        info!("Restored '{}' to '{}'", file.name, file.path.display());
+    }
+
+    prune(backup_dir, keep)?;
+    Ok(files)
+}
+
+/// What to write back for the copy of `file`. Copies are put back as they are unless the format has a checksum
+/// line that doesn't match the words; those are rendered again with the file's own layout so the app accepts them.
+fn restored_bytes(bytes: &[u8], file: &BackupFile) -> Result<Vec<u8>, Error> {
+    if !file.format.has_checksum() {
+        return Ok(bytes.to_vec());
+    }
+    let options = FormatOptions {
+        format: file.format,
+        ..FormatOptions::default()
+    };
+    let format = options.dictionary_format();
+    let parsed = format
+        .parse(&file.path, bytes)
+        .map_err(|err| Error::Encoding {
+            path: file.path.clone(),
+            source: err.into(),
+        })?;
+    if parsed.metadata.checksum == ChecksumStatus::Valid {
+        return Ok(bytes.to_vec());
+    }
+
+    debug!(
+        "Backup of '{}' has a checksum that doesn't match its words; writing a new one",
+        file.name
+    );
+    let mut words: Vec<&String> = parsed.words.iter().collect();
+    words.sort();
+    Ok(format
+        .serialize(&file.path, &words, &parsed.metadata, bytes)
+        .map_err(io::Error::from)?)
+}
+
+/// Dictionary names come from the config file and can contain just about anything
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{chromium, LineEnding, TextLayout};
+
+    #[test]
+    fn test_snapshot_and_list() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let dict_path = temp_dir.path().join("Custom Dictionary.txt");
+        fs::write(&dict_path, "alpha\nbeta\n")?;
+        let backup_dir = temp_dir.path().join("backups");
+
+        let mut run = BackupRun::new(&backup_dir)?;
+        run.snapshot("flat pak", &dict_path, Format::Chromium)?;
+        let dic_path = temp_dir.path().join("en_US.dic");
+        fs::write(&dic_path, "1\nalpha\n")?;
+        run.snapshot("hunspell", &dic_path, Format::Hunspell)?;
+        // Missing files are skipped
+        run.snapshot(
+            "missing",
+            &temp_dir.path().join("nope.txt"),
+            Format::Chromium,
+        )?;
+        let manifest = run.finish()?;
+
+        assert_eq!(manifest.files.len(), 2);
+        assert_eq!(manifest.files[0].backup, "flat_pak.txt");
+        // The copy keeps the original's extension
+        assert_eq!(manifest.files[1].backup, "hunspell.dic");
+
+        let runs = list_runs(&backup_dir)?;
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].run, manifest.run);
+        assert_eq!(runs[0].files[0].path, dict_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_keeps_newest() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut names = Vec::new();
+        for _ in 0..3 {
+            names.push(BackupRun::new(temp_dir.path())?.finish()?.run);
+        }
+
+        let removed = prune(temp_dir.path(), 2)?;
+        assert_eq!(removed, vec![names[0].clone()]);
+        let remaining: Vec<String> = list_runs(temp_dir.path())?
+            .into_iter()
+            .map(|manifest| manifest.run)
+            .collect();
+        assert_eq!(remaining, names[1..]);
+
+        // 0 means keep everything
+        assert!(prune(temp_dir.path(), 0)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        let dict_path = temp_dir.path().join("dict.txt");
+        // Hand edited; no checksum line, CRLF and a word with trailing whitespace
+        fs::write(&dict_path, "beta\r\nalpha \r\n")?;
+        let dic_path = temp_dir.path().join("en_US.dic");
+        fs::write(&dic_path, "2\nbeta\nalpha/MS\n")?;
+
+        let mut run = BackupRun::new(temp_dir.path())?;
+        run.snapshot("dict", &dict_path, Format::Chromium)?;
+        run.snapshot("hunspell", &dic_path, Format::Hunspell)?;
+        let manifest = run.finish()?;
+
+        fs::write(&dict_path, "something else entirely\n")?;
+        fs::write(&dic_path, "0\n")?;
+        // Finding the files does not touch them
+        let files = files_to_restore(temp_dir.path(), &manifest.run, None)?;
+        assert_eq!(files[0].path, dict_path);
+        assert_eq!(list_runs(temp_dir.path())?.len(), 1);
+        assert_eq!(fs::read_to_string(&dict_path)?, "something else entirely\n");
+
+        let restored = restore(temp_dir.path(), &manifest.run, None, 0)?;
+        assert_eq!(restored.len(), 2);
+        // The replaced contents were backed up as a new run
+        assert_eq!(list_runs(temp_dir.path())?.len(), 2);
+
+        // Same words and layout but now with a checksum line Obsidian accepts
+        let alpha = String::from("alpha ");
+        let beta = String::from("beta");
+        let layout = TextLayout {
+            line_ending: LineEnding::Crlf,
+            bom: false,
+        };
+        let expected = chromium::render(&[&alpha, &beta], &layout);
+        assert_eq!(fs::read(&dict_path)?, expected);
+        // Formats without a checksum come back as they were
+        assert_eq!(fs::read_to_string(&dic_path)?, "2\nbeta\nalpha/MS\n");
+
+        // A valid copy is put back as it is
+        let mut run = BackupRun::new(temp_dir.path())?;
+        run.snapshot("dict", &dict_path, Format::Chromium)?;
+        let valid = run.finish()?;
+        fs::write(&dict_path, "something else entirely\n")?;
+        restore(temp_dir.path(), &valid.run, Some("dict"), 0)?;
+        assert_eq!(fs::read(&dict_path)?, expected);
+
+        assert!(restore(temp_dir.path(), &manifest.run, Some("other"), 0).is_err());
+        assert!(restore(temp_dir.path(), "not-a-run", None, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_prunes_old_runs() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        let dict_path = temp_dir.path().join("dict.txt");
+        fs::write(&dict_path, "alpha\n")?;
+
+        let mut run = BackupRun::new(temp_dir.path())?;
+        run.snapshot("dict", &dict_path, Format::Chromium)?;
+        let manifest = run.finish()?;
+        let mut runs = vec![manifest.run.clone()];
+        for _ in 0..2 {
+            runs.push(BackupRun::new(temp_dir.path())?.finish()?.run);
+        }
+
+        // The undo run is the newest so it is one of the two kept
+        restore(temp_dir.path(), &manifest.run, None, 2)?;
+        let remaining: Vec<String> = list_runs(temp_dir.path())?
+            .into_iter()
+            .map(|manifest| manifest.run)
+            .collect();
+        assert_eq!(remaining.len(), 2);
+        assert_eq!(remaining[0], runs[2]);
+        assert!(!runs.contains(&remaining[1]));
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
 
 use crate::build_info;
 
@@ -15,11 +15,135 @@ pub struct Args {
     ///
     /// If not found, an example file with default values will be created at this location.
     ///
-    pub config_file_path: Option<PathBuf>,
+    pub config_file_path: PathBuf,
 
     #[arg(short, long, env = "ODS_LOG_VERBOSE")]
     /// Enable verbose logging
     pub verbose: bool,
+
+    #[arg(short = 'n', long, global = true)]
+    /// Show what would be added to / removed from each dictionary without writing anything
+    pub dry_run: bool,
+
+    #[command(subcommand)]
+    /// When no command is given, all dictionaries are synchronized
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List backup runs or put the dictionaries from one of them back in place
+    Restore {
+        /// Name of the backup run to restore. If omitted, the available runs are listed.
+        run: Option<String>,
+
+        #[arg(short, long)]
+        /// Only restore this dictionary from the run
+        dictionary: Option<String>,
+    },
+
+    /// Check the checksum line of every dictionary; exits non-zero if any are missing, wrong or duplicated
+    Verify,
+
+    /// Add words to the authoritative dictionary
+    Add {
+        #[arg(required = true)]
+        words: Vec<String>,
+
+        #[arg(short, long)]
+        /// Also add the words to every other dictionary now instead of waiting for the next sync
+        all: bool,
+    },
+
+    /// Remove words from the authoritative dictionary
+    Remove {
+        #[arg(required = true)]
+        words: Vec<String>,
+
+        #[arg(short, long)]
+        /// Also remove the words from every other dictionary now instead of waiting for the next sync
+        all: bool,
+    },
+
+    /// Show how each dictionary differs from what the next sync writes; exits non-zero if any are out of sync
+    Status {
+        #[arg(long)]
+        /// Print JSON instead of a table
+        json: bool,
+    },
+
+    /// Look for Obsidian (and other supported app) dictionaries in the usual Linux locations and print config entries
+    Discover,
+
+    /// List every word, sorted, along with the dictionaries it appears in
+    List {
+        #[command(flatten)]
+        query: QueryArgs,
+    },
+
+    /// Find words containing some text, matching a regex or spelled close to some text
+    Search {
+        /// Text (or regex with --regex) to look for
+        pattern: String,
+
+        #[arg(short, long, conflicts_with = "fuzzy")]
+        /// Treat the pattern as a regular expression
+        regex: bool,
+
+        #[arg(short, long)]
+        /// Find words within --max-distance typos of the pattern, closest first. Always ignores case.
+        fuzzy: bool,
+
+        #[arg(long, default_value_t = 2, requires = "fuzzy")]
+        /// How many single character edits a fuzzy match may be away from the pattern
+        max_distance: usize,
+
+        #[arg(short, long)]
+        /// Ignore upper/lower case
+        ignore_case: bool,
+
+        #[command(flatten)]
+        query: QueryArgs,
+    },
+}
+
+/// Options shared by the commands that only read dictionaries
+#[derive(ClapArgs, Debug)]
+pub struct QueryArgs {
+    #[arg(long = "in", value_name = "DICTIONARY")]
+    /// Only show words that are in this dictionary. Can be given more than once.
+    pub in_dictionaries: Vec<String>,
+
+    #[arg(long = "not-in", value_name = "DICTIONARY")]
+    /// Hide words that are in this dictionary. Can be given more than once.
+    pub not_in_dictionaries: Vec<String>,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+    /// How to print the results
+    pub format: OutputFormat,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// One word per line
+    Plain,
+    /// Array of `{"word": ..., "dictionaries": [...]}` objects
+    Json,
+    /// Only the number of words
+    Count,
+}
+
+impl Command {
+    /// Commands whose output is meant to be piped somewhere; logging must stay out of the way
+    pub fn is_query(&self) -> bool {
+        matches!(
+            self,
+            Command::Discover
+                | Command::Status { .. }
+                | Command::List { .. }
+                | Command::Search { .. }
+        )
+    }
 }
 
 //TODO: implement log level selection? For now, verbose on/off is good enough
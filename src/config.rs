@@ -1,106 +1,430 @@
-use log::{debug, error};
+use crate::discovery::{self, DiscoveryEnv};
+use crate::error::Error;
+use crate::filter::{Canonical, Filter, FilterOptions};
+use crate::formats::{Format, FormatOptions, LineEnding};
+use crate::presets::Preset;
+use crate::user_dictionary::get_hydrated_path_from_str;
+use crate::validation::{EncodingErrorPolicy, InvalidWordPolicy};
+use log::debug;
 use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
 use std::fs::File;
 use std::io::ErrorKind;
 use std::path::PathBuf;
-use std::{fs, process::exit};
-use toml::{self, Table};
+use toml::Spanned;
 
 /// Top level structure to hold entirety of the config file
 #[derive(Deserialize, Debug)]
 pub struct Config {
-    // TODO: instead of Table, this should be Dictionary type :/
-    pub dictionaries: Table,
+    /// Filled in by parse() so that errors can name the offending entry
+    #[serde(skip)]
+    pub dictionaries: BTreeMap<String, Dictionary>,
+    /// Filled in by parse() so that errors can name the offending pattern
+    #[serde(skip)]
     pub filters: Filters,
+    #[serde(default)]
+    pub backup: Backup,
+    /// Also sync every dictionary of a supported app found in the well known locations; see discovery.rs
+    #[serde(default)]
+    pub auto_discover: bool,
+    /// What to do with dictionary entries that are not valid words unless a dictionary says otherwise
+    #[serde(default)]
+    pub invalid_words: InvalidWordPolicy,
+    /// What to do with dictionary lines that are not valid UTF-8 unless a dictionary says otherwise
+    #[serde(default)]
+    pub encoding_errors: EncodingErrorPolicy,
 }
-/// Each dictionary has a name/path.
-/// The dictionary named "authoritative" will be the source of truth
-// #[derive(Deserialize, Debug)]
-// pub struct Dictionary {
-//     name: String,
-//     path: String,
-// }
 
-/// User can supply a list of words that they do not want in the final results
+/// Each dictionary has a name (the key in the config file), a path and some optional settings.
+/// The dictionary named "authoritative" will be the source of truth
 #[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Dictionary {
+    /// Where the dictionary file lives. `~` and environment variables are expanded.
+    /// Filled in by parse() from `preset` if not given.
+    #[serde(default)]
+    pub path: String,
+    /// Well known app whose dictionary this is; saves spelling out the path
+    #[serde(default)]
+    pub preset: Option<Preset>,
+    /// Browser profile for presets that have them. Defaults to `Default`.
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Disabled dictionaries are ignored entirely
+    #[serde(default = "default_dictionary_enabled")]
+    pub enabled: bool,
+    /// Words are read from a readonly dictionary but it is never written to
+    #[serde(default)]
+    pub readonly: bool,
+    /// Normally a dictionary file that does not exist is skipped. If set, it is created instead.
+    #[serde(default)]
+    pub create_if_missing: bool,
+    /// Layout of the file; defaults to the Chromium style used by Obsidian
+    #[serde(default)]
+    pub format: Format,
+    /// Language tag for formats that record one (aspell). Defaults to whatever the file already has.
+    #[serde(default)]
+    pub lang: Option<String>,
+    /// Dotted path to the words array for formats that nest them (json), e.g. `cSpell.words`
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Line ending to write (`lf` or `crlf`). Defaults to whatever the file already has.
+    #[serde(default)]
+    pub line_ending: Option<LineEnding>,
+    /// Whether to start the file with a UTF-8 BOM. Defaults to whatever the file already has.
+    #[serde(default)]
+    pub bom: Option<bool>,
+    /// What to do with entries that are not valid words. Filled in by parse() from the top level setting if not given.
+    #[serde(default)]
+    pub invalid_words: Option<InvalidWordPolicy>,
+    /// What to do with lines that are not valid UTF-8. Filled in by parse() from the top level setting if not given.
+    #[serde(default)]
+    pub encoding_errors: Option<EncodingErrorPolicy>,
+    /// Shell command run after the file is written, e.g. to rebuild a Vim `.spl`. The file's path is `$1`.
+    #[serde(default)]
+    pub post_write: Option<String>,
+}
+
+impl Dictionary {
+    /// How the dictionary's file is read and written
+    pub fn format_options(&self) -> FormatOptions {
+        FormatOptions {
+            format: self.format,
+            lang: self.lang.clone(),
+            key: self.key.clone(),
+            line_ending: self.line_ending,
+            bom: self.bom,
+            invalid_words: self.invalid_words.unwrap_or_default(),
+            encoding_errors: self.encoding_errors.unwrap_or_default(),
+        }
+    }
+}
+
+fn default_dictionary_enabled() -> bool {
+    true
+}
+
+/// Dictionaries are deserialized one at a time so an error can name the entry and line it came from.
+/// Toml on its own will only point at the offending key.
+#[derive(Deserialize)]
+struct RawDictionaries {
+    dictionaries: BTreeMap<String, Spanned<toml::Value>>,
+}
+
+/// Same idea as `RawDictionaries`; patterns are compiled one at a time so an error can name the entry and line.
+#[derive(Deserialize)]
+struct RawFilters {
+    filters: RawFilterList,
+}
+
+#[derive(Deserialize)]
+struct RawFilterList {
+    /// Each entry is either a plain pattern string or a `FilterOptions` table
+    remove: Vec<Spanned<toml::Value>>,
+    #[serde(default)]
+    canonical: Vec<String>,
+}
+
+/// User can supply a list of words (or patterns matching words) that they do not want in the final results
+#[derive(Debug, Default)]
 pub struct Filters {
-    pub remove: Vec<String>,
+    pub remove: Vec<Filter>,
+    /// Words whose other case variants are removed
+    pub canonical: Vec<Canonical>,
+}
+
+impl Filters {
+    /// True if any filter says `word` should be removed
+    pub fn matches(&self, word: &str) -> bool {
+        self.remove.iter().any(|filter| filter.matches(word))
+            || self
+                .canonical
+                .iter()
+                .any(|canonical| canonical.matches(word))
+    }
+}
+
+/// Before a dictionary is overwritten, a copy of it is kept in the backup directory
+#[derive(Deserialize, Debug)]
+pub struct Backup {
+    #[serde(default = "default_backup_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_backup_dir")]
+    pub dir: String,
+    /// How many runs worth of backups to keep. 0 keeps all of them.
+    #[serde(default = "default_backup_keep")]
+    pub keep: usize,
+}
+
+fn default_backup_enabled() -> bool {
+    true
+}
+
+fn default_backup_dir() -> String {
+    String::from("./backups")
+}
+
+fn default_backup_keep() -> usize {
+    10
+}
+
+impl Default for Backup {
+    fn default() -> Self {
+        Backup {
+            enabled: default_backup_enabled(),
+            dir: default_backup_dir(),
+            keep: default_backup_keep(),
+        }
+    }
 }
 
 const EXAMPLE_CONFIG_FILE: &str = include_str!("../config/example.toml");
 
 // TODO: tests!
 impl Config {
-    pub fn new_from_file(file_path: &PathBuf) -> Self {
+    pub fn new_from_file(file_path: &PathBuf) -> Result<Self, Error> {
         // TODO: support shell expansion with shellexpand
-        let file_path_str = file_path.to_str().unwrap();
+        let file_path_str = file_path.display();
 
         debug!("reading '{}'", file_path_str);
 
         // Would be nice to File::create_new but it's unstable :(
-        let content = fs::read_to_string(file_path).unwrap_or_else(|err| {
-            if err.kind() == ErrorKind::NotFound {
+        let config_io_err = |source| Error::ConfigIo {
+            path: file_path.clone(),
+            source,
+        };
+        let content = match fs::read_to_string(file_path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
                 warn!("Config file at '{}' not found. Creating....", file_path_str);
+                File::create(file_path).map_err(config_io_err)?;
+                fs::write(file_path, EXAMPLE_CONFIG_FILE).map_err(config_io_err)?;
+                String::from(EXAMPLE_CONFIG_FILE)
+            }
+            Err(err) => return Err(config_io_err(err)),
+        };
 
-                File::create(file_path).unwrap_or_else(|err| {
-                    error!("Could not create '{}'. Error: {}", file_path_str, err);
-                    exit(1);
-                });
+        let mut config = Self::parse(&content)?;
+        if config.auto_discover {
+            match DiscoveryEnv::from_env() {
+                Some(env) => {
+                    config.add_discovered(&env);
+                }
+                None => warn!("auto_discover is set but $HOME is not; nothing to discover"),
+            }
+        }
+        Ok(config)
+    }
 
-                fs::write(file_path, EXAMPLE_CONFIG_FILE).unwrap_or_else(|err| {
-                    error!("Failure to create '{}'/ Error: {}", file_path_str, err)
-                });
-                String::from(EXAMPLE_CONFIG_FILE)
-            } else {
-                error!("could not open '{}': {}", file_path_str, err);
-                exit(1);
+    /// Adds an entry for every discovered dictionary that is not already configured.
+    /// Returns the names of the added entries.
+    pub fn add_discovered(&mut self, env: &DiscoveryEnv) -> Vec<String> {
+        let mut added = Vec::new();
+        for found in discovery::discover(env, self) {
+            if found.configured_as.is_some() {
+                continue;
+            }
+            // Don't clobber a hand written entry that happens to use the same name
+            let mut name = found.name.clone();
+            let mut attempt = 1;
+            while self.dictionaries.contains_key(&name) {
+                attempt += 1;
+                name = format!("{}_{}", found.name, attempt);
+            }
+            // Loud on purpose; the dictionary is rewritten without the user having listed it
+            warn!(
+                "Discovered dictionary '{}' at '{}'; it is synced as well, adding its words but not removing any \
+                 until it has been synced once. Set `auto_discover = false` to opt out.",
+                name,
+                found.path.display()
+            );
+            self.dictionaries.insert(
+                name.clone(),
+                Dictionary {
+                    path: found.path.display().to_string(),
+                    preset: found.preset.as_ref().map(|(preset, _)| *preset),
+                    profile: found.preset.and_then(|(_, profile)| profile),
+                    enabled: true,
+                    readonly: false,
+                    create_if_missing: false,
+                    format: Format::Chromium,
+                    lang: None,
+                    key: None,
+                    line_ending: None,
+                    bom: None,
+                    invalid_words: Some(self.invalid_words),
+                    encoding_errors: Some(self.encoding_errors),
+                    post_write: None,
+                },
+            );
+            added.push(name);
+        }
+        added
+    }
+
+    /// Parses and sanity checks the content of a config file.
+    /// Errors name the offending entry and, where toml can tell us, the line it's on.
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        let parse_err = |err: toml::de::Error| Error::ConfigParse(err.to_string());
+        let mut config: Config = toml::from_str(content).map_err(parse_err)?;
+        let raw: RawDictionaries = toml::from_str(content).map_err(parse_err)?;
+        for (name, entry) in raw.dictionaries {
+            let line = line_number(content, entry.span().start);
+            let entry_err = |message: &str| {
+                Error::ConfigParse(format!(
+                    "dictionary `{}` (line {}): {}",
+                    name,
+                    line,
+                    message.trim()
+                ))
+            };
+            let mut dictionary = Dictionary::deserialize(entry.into_inner())
+                .map_err(|err| entry_err(err.message()))?;
+            match (dictionary.preset, dictionary.path.is_empty()) {
+                (Some(_), false) => {
+                    return Err(entry_err("set either `path` or `preset`, not both"))
+                }
+                (None, true) => return Err(entry_err("missing field `path` (or `preset`)")),
+                (Some(preset), true) => {
+                    if dictionary.profile.is_some() && !preset.has_profiles() {
+                        return Err(entry_err(&format!(
+                            "preset `{}` does not have profiles",
+                            preset.name()
+                        )));
+                    }
+                    dictionary.path = preset.path(dictionary.profile.as_deref());
+                }
+                (None, false) => {
+                    if dictionary.profile.is_some() {
+                        return Err(entry_err("`profile` only applies to a `preset`"));
+                    }
+                }
+            }
+            if let Some(lang) = &dictionary.lang {
+                if !dictionary.format.has_lang() {
+                    return Err(entry_err(
+                        "`lang` only applies to the aspell and libreoffice formats",
+                    ));
+                }
+                if lang.is_empty() || lang.contains(char::is_whitespace) {
+                    return Err(entry_err(&format!(
+                        "`lang` '{}' is not a language tag",
+                        lang
+                    )));
+                }
             }
-        });
+            match (&dictionary.key, dictionary.format.has_key()) {
+                (None, true) => {
+                    return Err(entry_err(
+                        "format `json` needs a `key`, e.g. `cSpell.words`",
+                    ))
+                }
+                (Some(_), false) => {
+                    return Err(entry_err("`key` only applies to `format = \"json\"`"))
+                }
+                (Some(key), true) if key.split('.').any(str::is_empty) => {
+                    return Err(entry_err(&format!("`key` '{}' has an empty segment", key)))
+                }
+                _ => {}
+            }
+            if (dictionary.line_ending.is_some() || dictionary.bom.is_some())
+                && !dictionary.format.has_text_layout()
+            {
+                return Err(entry_err(
+                    "`line_ending` and `bom` don't apply to `format = \"msword\"`, which is always UTF-16 with CRLF",
+                ));
+            }
+            dictionary.invalid_words.get_or_insert(config.invalid_words);
+            dictionary
+                .encoding_errors
+                .get_or_insert(config.encoding_errors);
+            config.dictionaries.insert(name, dictionary);
+        }
 
-        toml::from_str(&content).unwrap()
+        let raw: RawFilters = toml::from_str(content).map_err(parse_err)?;
+        for entry in raw.filters.remove {
+            let line = line_number(content, entry.span().start);
+            let options = match entry.into_inner() {
+                toml::Value::String(pattern) => Ok(FilterOptions {
+                    pattern,
+                    ignore_case: false,
+                    normalize: None,
+                }),
+                table => FilterOptions::deserialize(table)
+                    .map_err(|err| err.message().trim().to_string()),
+            };
+            let filter = options.and_then(Filter::new).map_err(|err| {
+                Error::ConfigParse(format!("filters.remove (line {}): {}", line, err))
+            })?;
+            config.filters.remove.push(filter);
+        }
+        config.filters.canonical = raw
+            .filters
+            .canonical
+            .into_iter()
+            .map(Canonical::new)
+            .collect();
+
+        let authoritative = config
+            .dictionaries
+            .get("authoritative")
+            .ok_or(Error::MissingAuthoritative)?;
+        if !authoritative.enabled {
+            return Err(Error::ConfigParse(String::from(
+                "dictionary `authoritative` can not be disabled; it is the source of truth",
+            )));
+        }
+        if authoritative.readonly {
+            return Err(Error::ConfigParse(String::from(
+                "dictionary `authoritative` can not be readonly; it is written on every run",
+            )));
+        }
+
+        Ok(config)
     }
 
     /// Gets canonical path to the authoritative dictionary
+    /// If the dictionary does not exist it is created unless `create_if_missing` is false in which case
+    /// the path is returned as configured.
     // TODO: tests!
-    pub fn get_authoritative_dictionary_path(&self) -> PathBuf {
-        let raw_path = self
-            .dictionaries
-            .get("authoritative")
-            .unwrap()
-            .as_table()
-            .unwrap()
-            .get("path")
-            .unwrap()
-            .as_str()
-            .unwrap();
-
-        let path = PathBuf::from(raw_path).canonicalize();
-        match path {
+    pub fn get_authoritative_dictionary_path(
+        &self,
+        create_if_missing: bool,
+    ) -> Result<PathBuf, Error> {
+        // parse() made sure this exists
+        let raw_path = get_hydrated_path_from_str(&self.dictionaries["authoritative"].path)?;
+
+        match raw_path.canonicalize() {
             Ok(p) => {
-                debug!("canonical path: {}", p.to_str().unwrap());
-                p
+                debug!("canonical path: {}", p.display());
+                Ok(p)
             }
-            Err(err) => {
-                // Ideally we'd be able to canonicalize() w/o testing that the file exists so we'd pass the cannon path into the create
-                // call. Instead, we pass in the raw path since i'm not writing my own canonicalize() function that doesn't check for
-                //  file existence.
-                if err.kind() == ErrorKind::NotFound {
-                    warn!(
-                        "Authoritative dictionary '{}' not found. Creating....",
-                        raw_path
-                    );
-                    File::create(raw_path).unwrap_or_else(|err| {
-                        panic!("Could not create '{}'. Error: {}", raw_path, err);
-                    });
-                    PathBuf::from(raw_path).canonicalize().unwrap()
-                } else {
-                    panic!("Other failure opening '{}': {}", raw_path, err);
-                }
+            // Ideally we'd be able to canonicalize() w/o testing that the file exists so we'd pass the cannon path into the create
+            // call. Instead, we pass in the raw path since i'm not writing my own canonicalize() function that doesn't check for
+            //  file existence.
+            Err(err) if err.kind() == ErrorKind::NotFound && !create_if_missing => Ok(raw_path),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                warn!(
+                    "Authoritative dictionary '{}' not found. Creating....",
+                    raw_path.display()
+                );
+                File::create(&raw_path).map_err(|source| Error::Write {
+                    path: raw_path.clone(),
+                    source,
+                })?;
+                Ok(raw_path.canonicalize()?)
             }
+            Err(err) => Err(Error::Io(err)),
         }
     }
 }
 
+/// 1-based line number of the byte offset `pos` in `content`
+fn line_number(content: &str, pos: usize) -> usize {
+    content[..pos.min(content.len())].matches('\n').count() + 1
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -109,9 +433,385 @@ mod tests {
     // That way I can also test out hash gen
     #[test]
     fn test_new_from_file() {
-        let cfg = Config::new_from_file(&PathBuf::from("config/example.toml"));
+        let cfg = Config::new_from_file(&PathBuf::from("config/example.toml")).unwrap();
         // TODO: check actual content, not just the length :)
         assert_eq!(cfg.dictionaries.len(), 5);
         assert_eq!(cfg.filters.remove.len(), 4);
+        assert_eq!(cfg.filters.canonical[0].word(), "FitBit");
+        assert!(cfg.backup.enabled);
+        assert_eq!(cfg.backup.dir, "./backups");
+        assert_eq!(cfg.backup.keep, 5);
+    }
+
+    #[test]
+    fn test_dictionary_settings() {
+        let cfg = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [dictionaries.laptop]
+            path = "~/Custom Dictionary.txt"
+            readonly = true
+            create_if_missing = true
+            [dictionaries.old]
+            path = "./old.txt"
+            enabled = false
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap();
+
+        let authoritative = &cfg.dictionaries["authoritative"];
+        assert_eq!(authoritative.path, "./master_dictionary.txt");
+        assert!(authoritative.enabled);
+        assert!(!authoritative.readonly);
+        assert!(!authoritative.create_if_missing);
+
+        let laptop = &cfg.dictionaries["laptop"];
+        assert!(laptop.readonly);
+        assert!(laptop.create_if_missing);
+        assert!(!cfg.dictionaries["old"].enabled);
+    }
+
+    #[test]
+    fn test_dictionary_errors_name_entry() {
+        let err = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [dictionaries.laptop]
+            pth = "./typo.txt"
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("dictionary `laptop` (line 4)"), "{}", err);
+        assert!(err.contains("pth"), "{}", err);
+
+        let err = Config::parse(
+            r#"
+            [dictionaries.laptop]
+            path = "./laptop.txt"
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::MissingAuthoritative));
+
+        let err = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            readonly = true
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("readonly"), "{}", err);
+    }
+
+    #[test]
+    fn test_dictionary_formats() {
+        let cfg = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [dictionaries.aspell]
+            path = "~/.aspell.en.pws"
+            format = "aspell"
+            lang = "en_GB"
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cfg.dictionaries["authoritative"].format, Format::Chromium);
+        assert_eq!(cfg.dictionaries["aspell"].format, Format::Aspell);
+        assert_eq!(cfg.dictionaries["aspell"].lang.as_deref(), Some("en_GB"));
+
+        let err = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            lang = "en"
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("`lang` only applies"), "{}", err);
+
+        let err = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [dictionaries.vscode]
+            path = "./.vscode/settings.json"
+            format = "json"
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.contains("dictionary `vscode` (line 4): format `json` needs a `key`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_validation_policies() {
+        let cfg = Config::parse(
+            r#"
+            invalid_words = "drop"
+            encoding_errors = "quarantine"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [dictionaries.laptop]
+            path = "./laptop.txt"
+            invalid_words = "keep"
+            encoding_errors = "strict"
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap();
+        let policy = |name: &str| cfg.dictionaries[name].format_options().invalid_words;
+        assert_eq!(policy("authoritative"), InvalidWordPolicy::Drop);
+        assert_eq!(policy("laptop"), InvalidWordPolicy::Keep);
+        let encoding = |name: &str| cfg.dictionaries[name].format_options().encoding_errors;
+        assert_eq!(encoding("authoritative"), EncodingErrorPolicy::Quarantine);
+        assert_eq!(encoding("laptop"), EncodingErrorPolicy::Strict);
+
+        let cfg = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.dictionaries["authoritative"]
+                .format_options()
+                .invalid_words,
+            InvalidWordPolicy::Trim
+        );
+    }
+
+    #[test]
+    fn test_dictionary_text_layout() {
+        let cfg = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            line_ending = "crlf"
+            bom = false
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap();
+        let options = cfg.dictionaries["authoritative"].format_options();
+        assert_eq!(options.line_ending, Some(LineEnding::Crlf));
+        assert_eq!(options.bom, Some(false));
+
+        let err = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [dictionaries.word]
+            path = "./CUSTOM.DIC"
+            format = "msword"
+            bom = true
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.contains("`line_ending` and `bom` don't apply"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_filter_patterns() {
+        let cfg = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [filters]
+            remove = ["aa", "re:^[A-Z]{2}$", "glob:*http*"]
+            "#,
+        )
+        .unwrap();
+        assert!(cfg.filters.matches("aa"));
+        assert!(cfg.filters.matches("ZZ"));
+        assert!(cfg.filters.matches("https://obsidian.md"));
+        assert!(!cfg.filters.matches("FitBit"));
+
+        let err = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [filters]
+            remove = [
+                "aa",
+                "re:[0-9a-f{32}",
+            ]
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.contains("filter `re:[0-9a-f{32}` is not a valid regex"),
+            "{}",
+            err
+        );
+        assert!(err.contains("line 7"), "{}", err);
+    }
+
+    #[test]
+    fn test_filter_options() {
+        let cfg = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [filters]
+            remove = [
+                { pattern = "zz", ignore_case = true },
+                { pattern = "re:^file$", normalize = "nfkc" },
+            ]
+            canonical = ["FitBit"]
+            "#,
+        )
+        .unwrap();
+        assert!(cfg.filters.matches("ZZ"));
+        assert!(cfg.filters.matches("\u{fb01}le"));
+        assert!(cfg.filters.matches("fitbit"));
+        assert!(!cfg.filters.matches("FitBit"));
+
+        let err = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [filters]
+            remove = [{ pattern = "zz", normalise = "nfc" }]
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("filters.remove (line 5)"), "{}", err);
+        assert!(err.contains("normalise"), "{}", err);
+    }
+
+    #[test]
+    fn test_dictionary_presets() {
+        let cfg = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [dictionaries.slack]
+            preset = "slack"
+            [dictionaries.work_browser]
+            preset = "chrome"
+            profile = "Profile 2"
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.dictionaries["slack"].path,
+            "~/.config/Slack/Custom Dictionary.txt"
+        );
+        assert_eq!(cfg.dictionaries["slack"].preset, Some(Preset::Slack));
+        assert_eq!(
+            cfg.dictionaries["work_browser"].path,
+            "~/.config/google-chrome/Profile 2/Custom Dictionary.txt"
+        );
+
+        for (entry, message) in [
+            ("preset = \"slak\"", "unknown variant `slak`"),
+            ("preset = \"slack\"\npath = \"./x.txt\"", "not both"),
+            (
+                "preset = \"slack\"\nprofile = \"Default\"",
+                "does not have profiles",
+            ),
+            ("enabled = true", "missing field `path`"),
+        ] {
+            let err = Config::parse(&format!(
+                "[dictionaries.authoritative]\npath = \"./m.txt\"\n[dictionaries.app]\n{}\n[filters]\nremove = []\n",
+                entry
+            ))
+            .unwrap_err()
+            .to_string();
+            assert!(err.contains("dictionary `app` (line 3)"), "{}", err);
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_add_discovered() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let env = DiscoveryEnv {
+            home: temp_dir.path().to_path_buf(),
+            xdg_config_home: None,
+        };
+        let flatpak = temp_dir
+            .path()
+            .join(".var/app/md.obsidian.Obsidian/config/obsidian/Custom Dictionary.txt");
+        fs::create_dir_all(flatpak.parent().unwrap()).unwrap();
+        fs::write(&flatpak, "").unwrap();
+
+        let mut cfg = Config::parse(
+            r#"
+            auto_discover = true
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [dictionaries.obsidian_flatpak]
+            path = "./some_other_file.txt"
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap();
+        assert!(cfg.auto_discover);
+        assert_eq!(cfg.add_discovered(&env), vec!["obsidian_flatpak_2"]);
+        assert_eq!(
+            cfg.dictionaries["obsidian_flatpak_2"].path,
+            flatpak.display().to_string()
+        );
+        // Already configured now
+        assert!(cfg.add_discovered(&env).is_empty());
+    }
+
+    #[test]
+    fn test_backup_defaults() {
+        let cfg = Config::parse(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [filters]
+            remove = []
+            "#,
+        )
+        .unwrap();
+        assert!(cfg.backup.enabled);
+        assert_eq!(cfg.backup.dir, "./backups");
+        assert_eq!(cfg.backup.keep, 10);
     }
 }
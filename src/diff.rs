@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::user_dictionary::UserDictionary;
+
+/// What would change if `target` words were written over the `current` dictionary
+#[derive(Debug)]
+pub struct DictionaryDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Digest on the checksum line the file has now; None if it doesn't have exactly one
+    pub old_checksum: Option<String>,
+    /// Digest on the checksum line that would be written; None for formats without a checksum line
+    pub new_checksum: Option<String>,
+}
+
+impl DictionaryDiff {
+    pub fn new(current: &UserDictionary, target: &UserDictionary) -> Self {
+        let current_words = &current.words;
+        let target_words = &target.words;
+
+        DictionaryDiff {
+            added: sorted_difference(target_words, current_words),
+            removed: sorted_difference(current_words, target_words),
+            // Written in the current dictionary's format and layout, not the target's
+            old_checksum: current.stored_checksum.clone(),
+            new_checksum: current.checksum_for(target_words),
+        }
+    }
+
+    /// True if writing the target would not change anything
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.old_checksum == self.new_checksum
+    }
+}
+
+impl fmt::Display for DictionaryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(new_checksum) = &self.new_checksum {
+            writeln!(
+                f,
+                "  checksum: {} -> {}",
+                self.old_checksum.as_deref().unwrap_or("missing"),
+                new_checksum
+            )?;
+        }
+        writeln!(
+            f,
+            "  {} added, {} removed",
+            self.added.len(),
+            self.removed.len()
+        )?;
+        for word in &self.added {
+            writeln!(f, "  + {}", word)?;
+        }
+        for word in &self.removed {
+            writeln!(f, "  - {}", word)?;
+        }
+        Ok(())
+    }
+}
+
+/// Words in `a` that are not in `b`, sorted so the output is stable
+fn sorted_difference(a: &HashSet<String>, b: &HashSet<String>) -> Vec<String> {
+    let mut words: Vec<String> = a.difference(b).cloned().collect();
+    words.sort();
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{chromium, Format, LineEnding, TextLayout};
+    use std::path::PathBuf;
+
+    fn dict(list: &[&str]) -> UserDictionary {
+        UserDictionary::new(
+            PathBuf::from("/dev/null"),
+            list.iter().map(|w| w.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_diff_added_and_removed() {
+        let diff = DictionaryDiff::new(&dict(&["b", "a", "old"]), &dict(&["a", "b", "new", "c"]));
+        assert_eq!(diff.added, vec!["c", "new"]);
+        assert_eq!(diff.removed, vec!["old"]);
+        // Not read from a file so there is no checksum line yet
+        assert_eq!(diff.old_checksum, None);
+        assert!(!diff.is_empty());
+        assert!(diff.to_string().starts_with(&format!(
+            "  checksum: missing -> {}\n",
+            diff.new_checksum.unwrap()
+        )));
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let mut current = dict(&["a", "b"]);
+        // Only the words are the same; the file still needs a checksum line
+        assert!(!DictionaryDiff::new(&current, &dict(&["b", "a"])).is_empty());
+
+        current.stored_checksum = Some(format!("{:?}", current.hash));
+        let diff = DictionaryDiff::new(&current, &dict(&["b", "a"]));
+        assert_eq!(diff.old_checksum, diff.new_checksum);
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_diff_checksum_in_target_layout() {
+        let mut current = dict(&["a"]);
+        current.layout = TextLayout {
+            line_ending: LineEnding::Crlf,
+            bom: true,
+        };
+        let target = dict(&["a", "b"]);
+        let diff = DictionaryDiff::new(&current, &target);
+        // What is written, not the plain `\n` digest
+        let a = String::from("a");
+        let b = String::from("b");
+        let written = chromium::render(&[&a, &b], &current.layout);
+        assert!(written.ends_with(diff.new_checksum.clone().unwrap().as_bytes()));
+        assert_ne!(diff.new_checksum, Some(format!("{:?}", target.hash)));
+
+        // Formats without a checksum line have no checksum row
+        current.format = Format::Hunspell;
+        let diff = DictionaryDiff::new(&current, &target);
+        assert_eq!(diff.new_checksum, None);
+        assert!(!diff.to_string().contains("checksum"));
+        assert!(DictionaryDiff::new(&current, &dict(&["a"])).is_empty());
+    }
+}
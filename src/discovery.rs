@@ -0,0 +1,269 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::presets::Preset;
+use crate::user_dictionary::get_hydrated_path_from_str;
+
+/// File name Obsidian (and every other Electron / Chromium app) uses for the user's dictionary
+pub const DICTIONARY_FILE_NAME: &str = "Custom Dictionary.txt";
+
+/// The parts of the environment that decide where dictionaries live.
+/// Passed around explicitly so tests can point discovery at a temp dir instead of the real `$HOME`.
+#[derive(Debug, Clone)]
+pub struct DiscoveryEnv {
+    pub home: PathBuf,
+    /// `$XDG_CONFIG_HOME`, if set
+    pub xdg_config_home: Option<PathBuf>,
+}
+
+impl DiscoveryEnv {
+    /// Reads `$HOME` and `$XDG_CONFIG_HOME`. None if there is no home directory to search.
+    pub fn from_env() -> Option<Self> {
+        let home = env::var_os("HOME").filter(|home| !home.is_empty())?;
+        Some(DiscoveryEnv {
+            home: PathBuf::from(home),
+            xdg_config_home: env::var_os("XDG_CONFIG_HOME")
+                .filter(|xdg| !xdg.is_empty())
+                .map(PathBuf::from),
+        })
+    }
+}
+
+/// A dictionary file found in one of the well known locations
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discovered {
+    /// Suggested name for the `[dictionaries.<name>]` entry
+    pub name: String,
+    pub path: PathBuf,
+    /// Preset (and browser profile) that points at exactly this file. None if the file was found somewhere the
+    /// preset does not look, e.g. under a custom `$XDG_CONFIG_HOME`.
+    pub preset: Option<(Preset, Option<String>)>,
+    /// Name of the configured dictionary that already points at this file, if any
+    pub configured_as: Option<String>,
+}
+
+/// Every place a Linux install of a supported app keeps its dictionary.
+/// Browser profiles are only listed if the profile directory exists; otherwise the file need not exist.
+pub fn candidates(env: &DiscoveryEnv) -> Vec<Discovered> {
+    let default_config = env.home.join(".config");
+    let xdg = env
+        .xdg_config_home
+        .as_ref()
+        .filter(|xdg| **xdg != default_config);
+
+    let mut candidates = Vec::new();
+    for preset in Preset::ALL {
+        let user_data_dir = env.home.join(preset.user_data_dir());
+        candidates.extend(in_user_data_dir(
+            preset,
+            &user_data_dir,
+            preset.name(),
+            true,
+        ));
+
+        // Apps installed natively honor a custom XDG_CONFIG_HOME
+        if let (Some(xdg), Ok(relative)) = (xdg, user_data_dir.strip_prefix(&default_config)) {
+            let name = format!("{}_xdg", preset.name());
+            candidates.extend(in_user_data_dir(preset, &xdg.join(relative), &name, false));
+        }
+    }
+    candidates
+}
+
+/// Dictionaries in a single user data directory; one per profile for browsers
+fn in_user_data_dir(
+    preset: Preset,
+    user_data_dir: &Path,
+    name: &str,
+    is_preset_location: bool,
+) -> Vec<Discovered> {
+    if !preset.has_profiles() {
+        return vec![Discovered {
+            name: name.to_string(),
+            path: user_data_dir.join(DICTIONARY_FILE_NAME),
+            preset: is_preset_location.then_some((preset, None)),
+            configured_as: None,
+        }];
+    }
+
+    let mut profiles: Vec<String> = fs::read_dir(user_data_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().to_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    profiles.sort();
+    profiles
+        .into_iter()
+        .map(|profile| Discovered {
+            // `Profile 1` => `chrome_profile_1`
+            name: format!("{}_{}", name, profile.to_lowercase().replace(' ', "_")),
+            path: user_data_dir.join(&profile).join(DICTIONARY_FILE_NAME),
+            preset: is_preset_location.then(|| (preset, Some(profile.clone()))),
+            configured_as: None,
+        })
+        .collect()
+}
+
+/// Finds the dictionary files that exist and notes which of them `config` already has an entry for
+pub fn discover(env: &DiscoveryEnv, config: &Config) -> Vec<Discovered> {
+    candidates(env)
+        .into_iter()
+        .filter(|candidate| candidate.path.is_file())
+        .map(|candidate| Discovered {
+            configured_as: configured_name(config, &candidate.path),
+            ..candidate
+        })
+        .collect()
+}
+
+/// Renders discovered dictionaries as `[dictionaries.*]` entries that can be pasted into the config file.
+/// Dictionaries in a preset's location use the preset; otherwise paths under the home directory are written
+/// with `~` so the entry works for other users too.
+pub fn to_config_entries(env: &DiscoveryEnv, discovered: &[Discovered]) -> String {
+    let mut entries = Vec::new();
+    for found in discovered {
+        let mut entry = String::new();
+        if let Some(existing) = &found.configured_as {
+            entry.push_str(&format!("# already configured as `{}`\n", existing));
+        }
+        entry.push_str(&format!("[dictionaries.{}]\n", found.name));
+        match &found.preset {
+            Some((preset, profile)) => {
+                entry.push_str(&format!("preset = \"{}\"\n", preset.name()));
+                if let Some(profile) = profile {
+                    entry.push_str(&format!(
+                        "profile = {}\n",
+                        toml::Value::String(profile.clone())
+                    ));
+                }
+            }
+            None => {
+                let path = match found.path.strip_prefix(&env.home) {
+                    Ok(relative) => format!("~/{}", relative.display()),
+                    Err(_) => found.path.display().to_string(),
+                };
+                entry.push_str(&format!("path = {}\n", toml::Value::String(path)));
+            }
+        }
+        entries.push(entry);
+    }
+    entries.join("\n")
+}
+
+/// Name of the configured dictionary whose path resolves to `path`
+fn configured_name(config: &Config, path: &Path) -> Option<String> {
+    let canonical = path.canonicalize().ok()?;
+    config
+        .dictionaries
+        .iter()
+        .find(|(_, dictionary)| {
+            get_hydrated_path_from_str(&dictionary.path)
+                .ok()
+                .and_then(|p| p.canonicalize().ok())
+                .is_some_and(|p| p == canonical)
+        })
+        .map(|(name, _)| name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn fake_home() -> (tempfile::TempDir, DiscoveryEnv) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let env = DiscoveryEnv {
+            home: temp_dir.path().to_path_buf(),
+            xdg_config_home: Some(temp_dir.path().join("xdg")),
+        };
+        (temp_dir, env)
+    }
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn test_discover_known_locations() {
+        let (temp_dir, env) = fake_home();
+        let home = temp_dir.path();
+        touch(&home.join(".var/app/md.obsidian.Obsidian/config/obsidian/Custom Dictionary.txt"));
+        touch(&home.join("snap/obsidian/current/.config/obsidian/Custom Dictionary.txt"));
+        touch(&home.join("xdg/obsidian/Custom Dictionary.txt"));
+        touch(&home.join(".config/Slack/Custom Dictionary.txt"));
+        touch(&home.join(".config/google-chrome/Profile 1/Custom Dictionary.txt"));
+        // Profile without a dictionary yet
+        fs::create_dir_all(home.join(".config/google-chrome/Default")).unwrap();
+
+        let config = Config::parse(&format!(
+            r#"
+            [dictionaries.authoritative]
+            path = "./master_dictionary.txt"
+            [dictionaries.laptop]
+            path = "{}/snap/obsidian/current/.config/obsidian/Custom Dictionary.txt"
+            [filters]
+            remove = []
+            "#,
+            home.display()
+        ))
+        .unwrap();
+
+        let found = discover(&env, &config);
+        let names: Vec<&str> = found.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "obsidian_xdg",
+                "obsidian_flatpak",
+                "obsidian_snap",
+                "chrome_profile_1",
+                "slack"
+            ]
+        );
+        assert_eq!(found[0].preset, None);
+        assert_eq!(found[1].configured_as, None);
+        assert_eq!(found[2].configured_as, Some(String::from("laptop")));
+        assert_eq!(
+            found[3].preset,
+            Some((Preset::Chrome, Some(String::from("Profile 1"))))
+        );
+    }
+
+    #[test]
+    fn test_to_config_entries() {
+        let (temp_dir, env) = fake_home();
+        let found = vec![
+            Discovered {
+                name: String::from("chrome_profile_1"),
+                path: temp_dir
+                    .path()
+                    .join(".config/google-chrome/Profile 1/Custom Dictionary.txt"),
+                preset: Some((Preset::Chrome, Some(String::from("Profile 1")))),
+                configured_as: Some(String::from("browser")),
+            },
+            Discovered {
+                name: String::from("obsidian_xdg"),
+                path: temp_dir.path().join("xdg/obsidian/Custom Dictionary.txt"),
+                preset: None,
+                configured_as: None,
+            },
+        ];
+        assert_eq!(
+            to_config_entries(&env, &found),
+            "# already configured as `browser`\n\
+             [dictionaries.chrome_profile_1]\n\
+             preset = \"chrome\"\n\
+             profile = \"Profile 1\"\n\
+             \n\
+             [dictionaries.obsidian_xdg]\n\
+             path = \"~/xdg/obsidian/Custom Dictionary.txt\"\n"
+        );
+    }
+}
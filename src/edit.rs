@@ -0,0 +1,304 @@
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::diff::DictionaryDiff;
+use crate::error::Error;
+use crate::merge;
+use crate::sync::{
+    backup_before_write, load_authoritative, load_dictionary, run_post_write, Loaded, SyncOutcome,
+};
+use crate::user_dictionary::UserDictionary;
+use crate::validation;
+
+/// A change to the word list made from the command line rather than through Obsidian
+#[derive(Debug, Clone)]
+pub enum Edit {
+    Add(Vec<String>),
+    Remove(Vec<String>),
+}
+
+impl Edit {
+    /// Applies the edit to `dictionary`; returns how many words actually changed
+    fn apply(&self, dictionary: &mut UserDictionary) -> usize {
+        match self {
+            Edit::Add(words) => dictionary.add_words(words),
+            Edit::Remove(words) => dictionary.remove_words(words),
+        }
+    }
+}
+
+/// Adds or removes words in the authoritative dictionary.
+///
+/// Without `fan_out`, the change reaches the other dictionaries on the next sync. Additions always do; removals
+/// only do if the word was in the dictionaries as of the last sync (see `merge::three_way_merge`).
+///
+/// With `fan_out`, every other enabled, writable dictionary that exists (and the base snapshot) gets the same
+/// edit right away so the change does not depend on the merge.
+pub fn edit(
+    config: &Config,
+    edit: &Edit,
+    fan_out: bool,
+    dry_run: bool,
+) -> Result<Vec<(String, SyncOutcome)>, Error> {
+    // Words from the command line are held to the same rules as the words read from the authoritative dictionary
+    let edit = &match edit {
+        Edit::Add(words) => Edit::Add(valid_words(config, words)?),
+        Edit::Remove(_) => edit.clone(),
+    };
+    if let Edit::Add(words) = edit {
+        for word in words.iter().filter(|word| config.filters.matches(word)) {
+            warn!(
+                "'{}' matches a filter; the next sync will remove it again",
+                word
+            );
+        }
+    }
+
+    // Authoritative dictionary is always edited; create it if it does not exist yet
+    let authoritative_path = config.get_authoritative_dictionary_path(!dry_run)?;
+    let authoritative_dict = load_authoritative(config, authoritative_path)?;
+
+    let base_path = merge::base_snapshot_path(&authoritative_dict.path);
+
+    let mut report: Vec<(String, SyncOutcome)> = Vec::new();
+    let mut targets: Vec<(String, UserDictionary)> =
+        vec![(String::from("authoritative"), authoritative_dict)];
+    for (name, dictionary) in config.dictionaries.iter() {
+        if name == "authoritative" || !fan_out {
+            continue;
+        }
+        let outcome = match load_dictionary(name, dictionary)? {
+            Loaded::Disabled => SyncOutcome::Disabled,
+            Loaded::Skipped(reason) => SyncOutcome::Skipped { reason },
+            Loaded::New(ud) => SyncOutcome::Skipped {
+                reason: format!(
+                    "'{}' does not exist yet; the next sync creates it",
+                    ud.path.display()
+                ),
+            },
+            Loaded::Existing(ud) if dictionary.readonly => SyncOutcome::Readonly {
+                diff: DictionaryDiff::new(&ud, &edited(&ud, edit)),
+                path: ud.path,
+            },
+            Loaded::Existing(ud) => {
+                targets.push((name.clone(), ud));
+                continue;
+            }
+        };
+        report.push((name.clone(), outcome));
+    }
+
+    if !dry_run {
+        backup_before_write(config, targets.iter().map(|(name, ud)| (name.as_str(), ud)))?;
+    }
+
+    for (name, mut ud) in targets {
+        let target = edited(&ud, edit);
+        let diff = DictionaryDiff::new(&ud, &target);
+        if !dry_run {
+            ud.set_words(target.words);
+            ud.write_to_disk()?;
+            run_post_write(&name, &config.dictionaries[&name], &ud.path);
+        }
+        report.push((
+            name,
+            SyncOutcome::Updated {
+                path: ud.path,
+                diff,
+            },
+        ));
+    }
+
+    // Dictionaries now agree on this change so the base snapshot should too; otherwise the next merge could
+    // mistake it for an edit made in one place only
+    if fan_out && !dry_run && base_path.exists() {
+        let mut base_dict = merge::read_base_snapshot(base_path)?;
+        edit.apply(&mut base_dict);
+        base_dict.write_to_disk()?;
+    }
+
+    // Same order as a sync report: alphabetical by dictionary name
+    report.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(report)
+}
+
+/// Applies the authoritative dictionary's `invalid_words` policy to words given on the command line
+fn valid_words(config: &Config, words: &[String]) -> Result<Vec<String>, Error> {
+    let policy = config.dictionaries["authoritative"]
+        .format_options()
+        .invalid_words;
+    let (valid, report) =
+        validation::validate(words.iter().cloned().collect(), policy).map_err(|entries| {
+            Error::InvalidEntries {
+                path: PathBuf::from("command line"),
+                entries,
+            }
+        })?;
+    for entry in &report.trimmed {
+        warn!("Adding {:?} with its surrounding whitespace trimmed", entry);
+    }
+    for (entry, problem) in &report.dropped {
+        warn!(
+            "Not adding {:?}; it is not a valid word ({})",
+            entry, problem
+        );
+    }
+    for (entry, problem) in &report.kept {
+        warn!(
+            "Adding {:?} even though it is not a valid word ({})",
+            entry, problem
+        );
+    }
+    let mut valid: Vec<String> = valid.into_iter().collect();
+    valid.sort();
+    Ok(valid)
+}
+
+/// Copy of `dictionary` in memory with `edit` applied
+fn edited(dictionary: &UserDictionary, edit: &Edit) -> UserDictionary {
+    let mut edited = UserDictionary::new(dictionary.path.clone(), dictionary.words.clone());
+    let changed = edit.apply(&mut edited);
+    debug!(
+        "'{}' words changed in '{}'",
+        changed,
+        dictionary.path.display()
+    );
+    edited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::sync;
+    use crate::test_util::{config_for, words};
+    use std::collections::HashSet;
+    use std::fs;
+    use std::path::Path;
+
+    /// `a` and a readonly `ro` next to the master, without backups
+    const CONFIG: &str = r#"
+        [dictionaries.a]
+        path = "{dir}/a.txt"
+        [dictionaries.ro]
+        path = "{dir}/ro.txt"
+        readonly = true
+        [filters]
+        remove = []
+        [backup]
+        enabled = false
+    "#;
+
+    fn words_in(path: &Path) -> HashSet<String> {
+        UserDictionary::new_from_pathbuf(path.to_path_buf())
+            .unwrap()
+            .words
+    }
+
+    #[test]
+    fn test_add_only_touches_authoritative() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+
+        let report = edit(
+            &config,
+            &Edit::Add(vec![String::from("beta")]),
+            false,
+            false,
+        )?;
+        assert_eq!(report.len(), 1);
+        assert_eq!(
+            words_in(&temp_dir.path().join("master.txt")),
+            words(&["beta"])
+        );
+        assert_eq!(words_in(&temp_dir.path().join("a.txt")), words(&["alpha"]));
+
+        // Next sync carries the addition everywhere
+        sync(&config)?;
+        assert_eq!(
+            words_in(&temp_dir.path().join("a.txt")),
+            words(&["alpha", "beta"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_fans_out() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\ntypo\n")?;
+        fs::write(temp_dir.path().join("ro.txt"), "typo\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+        sync(&config)?;
+
+        let report = edit(
+            &config,
+            &Edit::Remove(vec![String::from("typo")]),
+            true,
+            false,
+        )?;
+        let names: Vec<&str> = report.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["a", "authoritative", "ro"]);
+        match &report[2].1 {
+            SyncOutcome::Readonly { diff, .. } => assert_eq!(diff.removed, vec!["typo"]),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        assert_eq!(words_in(&temp_dir.path().join("a.txt")), words(&["alpha"]));
+        assert_eq!(
+            words_in(&temp_dir.path().join("master.txt.base")),
+            words(&["alpha"])
+        );
+        // Readonly dictionaries are never written
+        assert_eq!(words_in(&temp_dir.path().join("ro.txt")), words(&["typo"]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_validates_words() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        let config = config_for(temp_dir.path(), CONFIG);
+        let added = |words: &[&str]| Edit::Add(words.iter().map(|w| w.to_string()).collect());
+
+        // Trimmed by default, same as a word read from a file
+        edit(
+            &config,
+            &added(&[" padded\t", "two words", ""]),
+            false,
+            false,
+        )?;
+        assert_eq!(
+            words_in(&temp_dir.path().join("master.txt")),
+            words(&["padded"])
+        );
+
+        let config = Config::parse(&format!(
+            "invalid_words = \"reject\"\n[dictionaries.authoritative]\npath = \"{}/master.txt\"\n[filters]\nremove = []\n",
+            temp_dir.path().display()
+        ))?;
+        let err = edit(&config, &added(&["fine", "two words"]), false, false).unwrap_err();
+        assert_eq!(err.exit_code(), 11);
+        assert_eq!(
+            words_in(&temp_dir.path().join("master.txt")),
+            words(&["padded"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run_edit_writes_nothing() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+
+        let report = edit(&config, &Edit::Add(vec![String::from("beta")]), true, true)?;
+        match &report[0].1 {
+            SyncOutcome::Updated { diff, .. } => assert_eq!(diff.added, vec!["beta"]),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        assert!(!temp_dir.path().join("master.txt").exists());
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("a.txt"))?,
+            "alpha\n"
+        );
+        Ok(())
+    }
+}
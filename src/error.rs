@@ -0,0 +1,184 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::validation::InvalidEntry;
+
+/// Everything that can go wrong while syncing dictionaries.
+///
+/// Each variant maps to a distinct process exit code so that whatever spawned the binary (e.g. the Templater user
+/// script) can tell failure modes apart without parsing log output:
+///
+/// | Code | Meaning                                                    |
+/// |------|------------------------------------------------------------|
+/// | 0    | Success                                                    |
+/// | 1    | Some other I/O failure (reading a dictionary, backups...)  |
+/// | 2    | Invalid command line arguments (reported by clap)          |
+/// | 3    | Config file could not be read, created or parsed           |
+/// | 4    | Config file has no `authoritative` dictionary              |
+/// | 5    | A path could not be expanded (e.g. unset env var)          |
+/// | 6    | A dictionary file is not valid text                        |
+/// | 7    | A dictionary file could not be written                     |
+/// | 8    | One or more dictionaries have a bad checksum line          |
+/// | 9    | Command line names a dictionary not in the config          |
+/// | 10   | One or more dictionaries are out of sync (`status`)        |
+/// | 11   | A dictionary has invalid entries and its policy is `reject`|
+#[derive(Debug)]
+pub enum Error {
+    /// Catch-all for I/O that doesn't fit anywhere more specific
+    Io(io::Error),
+    /// Config file could not be read or the example config could not be created
+    ConfigIo { path: PathBuf, source: io::Error },
+    /// Config file is not valid; message names the offending entry
+    ConfigParse(String),
+    /// Config file does not have a dictionary named `authoritative`
+    MissingAuthoritative,
+    /// `~` or an environment variable in a path could not be expanded
+    PathExpansion { path: String, message: String },
+    /// Dictionary file could not be decoded as text
+    Encoding { path: PathBuf, source: io::Error },
+    /// Dictionary file could not be written
+    Write { path: PathBuf, source: io::Error },
+    /// This many dictionaries have a checksum line that Obsidian will reject
+    ChecksumMismatch(usize),
+    /// Command line refers to a dictionary name that the config file does not have
+    UnknownDictionary(String),
+    /// This many dictionaries differ from the authoritative dictionary
+    OutOfSync(usize),
+    /// Dictionary file has entries that are not valid words and `invalid_words = "reject"`
+    InvalidEntries {
+        path: PathBuf,
+        entries: Vec<InvalidEntry>,
+    },
+}
+
+impl Error {
+    /// Process exit code for this error; see the table on [`Error`]
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(_) => 1,
+            Error::ConfigIo { .. } | Error::ConfigParse(_) => 3,
+            Error::MissingAuthoritative => 4,
+            Error::PathExpansion { .. } => 5,
+            Error::Encoding { .. } => 6,
+            Error::Write { .. } => 7,
+            Error::ChecksumMismatch(_) => 8,
+            Error::UnknownDictionary(_) => 9,
+            Error::OutOfSync(_) => 10,
+            Error::InvalidEntries { .. } => 11,
+        }
+    }
+
+    /// True if this is an I/O error for a file that does not exist
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Error::Io(err) if err.kind() == io::ErrorKind::NotFound)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::ConfigIo { path, source } => {
+                write!(
+                    f,
+                    "could not open config file '{}': {}",
+                    path.display(),
+                    source
+                )
+            }
+            Error::ConfigParse(message) => write!(f, "invalid config file: {}", message),
+            Error::MissingAuthoritative => write!(
+                f,
+                "the config file must have a dictionary named `authoritative` present"
+            ),
+            Error::PathExpansion { path, message } => {
+                write!(f, "could not expand path '{}': {}", path, message)
+            }
+            Error::Encoding { path, source } => {
+                write!(f, "'{}' is not valid text: {}", path.display(), source)
+            }
+            Error::Write { path, source } => {
+                write!(f, "could not write '{}': {}", path.display(), source)
+            }
+            Error::ChecksumMismatch(count) => {
+                write!(f, "'{}' dictionaries have an inconsistent checksum", count)
+            }
+            Error::UnknownDictionary(name) => {
+                write!(f, "the config file has no dictionary named `{}`", name)
+            }
+            Error::OutOfSync(count) => write!(f, "'{}' dictionaries are out of sync", count),
+            Error::InvalidEntries { path, entries } => {
+                write!(
+                    f,
+                    "'{}' has '{}' entries that are not valid words:",
+                    path.display(),
+                    entries.len()
+                )?;
+                for (entry, problem) in entries {
+                    write!(f, " {:?} ({})", entry, problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err)
+            | Error::ConfigIo { source: err, .. }
+            | Error::Encoding { source: err, .. }
+            | Error::Write { source: err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::Problem;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            Error::Io(io::Error::other("x")),
+            Error::ConfigParse(String::from("x")),
+            Error::MissingAuthoritative,
+            Error::PathExpansion {
+                path: String::from("$NOPE"),
+                message: String::from("x"),
+            },
+            Error::Encoding {
+                path: PathBuf::from("x"),
+                source: io::Error::other("x"),
+            },
+            Error::Write {
+                path: PathBuf::from("x"),
+                source: io::Error::other("x"),
+            },
+            Error::ChecksumMismatch(1),
+            Error::UnknownDictionary(String::from("x")),
+            Error::OutOfSync(1),
+            Error::InvalidEntries {
+                path: PathBuf::from("x"),
+                entries: vec![(String::from("x y"), Problem::Whitespace)],
+            },
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(Error::exit_code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        // 0 is success and 2 belongs to clap
+        assert!(!codes.contains(&0));
+        assert!(!codes.contains(&2));
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt;
+
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+
+/// Prefix for entries in `filters.remove` that are regular expressions
+const REGEX_PREFIX: &str = "re:";
+/// Prefix for entries in `filters.remove` that are shell style globs
+const GLOB_PREFIX: &str = "glob:";
+
+/// Unicode normalization form applied to words (and exact patterns) before they are compared.
+/// Without it, `café` typed as one code point and `café` typed as `e` + combining accent are different words.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Canonical composition; only merges different encodings of the same character
+    Nfc,
+    /// Compatibility composition; also merges look-alikes such as ligatures (`ﬁ` => `fi`) and full width letters
+    Nfkc,
+}
+
+impl Normalization {
+    pub fn apply(&self, word: &str) -> String {
+        match self {
+            Normalization::Nfc => word.nfc().collect(),
+            Normalization::Nfkc => word.nfkc().collect(),
+        }
+    }
+}
+
+/// A `filters.remove` entry written as a table instead of a plain string.
+/// A plain string is the same as a table with only `pattern` set.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FilterOptions {
+    pub pattern: String,
+    /// Match regardless of upper/lower case
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// Normalize words before matching
+    #[serde(default)]
+    pub normalize: Option<Normalization>,
+}
+
+/// How a filter decides that a word matches
+#[derive(Debug, Clone)]
+enum Matcher {
+    /// Already folded / normalized the same way words will be
+    Exact(String),
+    Regex(Regex),
+    Glob(Pattern),
+}
+
+/// A single entry in `filters.remove`.
+///
+/// - `re:<regex>` removes every word the regex matches. Like `grep`, the regex can match anywhere in the word;
+///   use `^` and `$` to match the whole word.
+/// - `glob:<pattern>` removes every word the glob matches. Globs always match the whole word.
+/// - Anything else removes that exact word.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    /// As written in the config file
+    pattern: String,
+    matcher: Matcher,
+    ignore_case: bool,
+    normalize: Option<Normalization>,
+}
+
+impl Filter {
+    pub fn new(options: FilterOptions) -> Result<Self, String> {
+        let FilterOptions {
+            pattern,
+            ignore_case,
+            normalize,
+        } = options;
+
+        let matcher = if let Some(regex) = pattern.strip_prefix(REGEX_PREFIX) {
+            let regex = RegexBuilder::new(regex)
+                .case_insensitive(ignore_case)
+                .build()
+                .map_err(|err| {
+                    format!(
+                        "filter `{}` is not a valid regex: {}",
+                        pattern,
+                        regex_reason(&err)
+                    )
+                })?;
+            Matcher::Regex(regex)
+        } else if let Some(glob) = pattern.strip_prefix(GLOB_PREFIX) {
+            let glob = Pattern::new(glob)
+                .map_err(|err| format!("filter `{}` is not a valid glob: {}", pattern, err))?;
+            Matcher::Glob(glob)
+        } else {
+            Matcher::Exact(prepare(&pattern, ignore_case, normalize))
+        };
+
+        Ok(Filter {
+            pattern,
+            matcher,
+            ignore_case,
+            normalize,
+        })
+    }
+
+    /// True if `word` should be removed
+    pub fn matches(&self, word: &str) -> bool {
+        match &self.matcher {
+            Matcher::Exact(exact) => *exact == prepare(word, self.ignore_case, self.normalize),
+            // Regex does its own case folding so only normalize
+            Matcher::Regex(regex) => regex.is_match(&prepare(word, false, self.normalize)),
+            Matcher::Glob(pattern) => pattern.matches_with(
+                &prepare(word, false, self.normalize),
+                MatchOptions {
+                    case_sensitive: !self.ignore_case,
+                    ..MatchOptions::new()
+                },
+            ),
+        }
+    }
+}
+
+impl TryFrom<String> for Filter {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        Filter::new(FilterOptions {
+            pattern,
+            ignore_case: false,
+            normalize: None,
+        })
+    }
+}
+
+/// Normalizes and/or case folds `word` so it can be compared
+fn prepare(word: &str, ignore_case: bool, normalize: Option<Normalization>) -> String {
+    let word = match normalize {
+        Some(normalization) => normalization.apply(word),
+        None => word.to_string(),
+    };
+    if ignore_case {
+        word.to_lowercase()
+    } else {
+        word
+    }
+}
+
+/// Regex syntax errors are a multi-line drawing of where the pattern went wrong; the last line says why
+fn regex_reason(err: &regex::Error) -> String {
+    let message = err.to_string();
+    message
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .map(|line| line.trim_start_matches("error: ").to_string())
+        .unwrap_or(message)
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)?;
+        if self.ignore_case {
+            write!(f, " (ignore case)")?;
+        }
+        if let Some(normalization) = self.normalize {
+            write!(f, " ({:?})", normalization)?;
+        }
+        Ok(())
+    }
+}
+
+/// An entry in `filters.canonical`: the one spelling of a word that is allowed to stay.
+/// Every other case (or NFC) variant of it is removed, e.g. `FitBit` removes `Fitbit`, `fitbit` and `FITBIT`.
+#[derive(Debug, Clone)]
+pub struct Canonical {
+    word: String,
+    folded: String,
+}
+
+impl Canonical {
+    pub fn new(word: String) -> Self {
+        Canonical {
+            folded: prepare(&word, true, Some(Normalization::Nfc)),
+            word,
+        }
+    }
+
+    /// The spelling that is kept
+    pub fn word(&self) -> &str {
+        &self.word
+    }
+
+    /// True if `word` is a variant of the canonical spelling but not the canonical spelling itself
+    pub fn matches(&self, word: &str) -> bool {
+        word != self.word && prepare(word, true, Some(Normalization::Nfc)) == self.folded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(entry: &str) -> Filter {
+        Filter::try_from(entry.to_string()).unwrap()
+    }
+
+    fn filter_with(pattern: &str, ignore_case: bool, normalize: Option<Normalization>) -> Filter {
+        Filter::new(FilterOptions {
+            pattern: pattern.to_string(),
+            ignore_case,
+            normalize,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_filter_kinds() {
+        assert!(filter("aa").matches("aa"));
+        assert!(!filter("aa").matches("aaa"));
+
+        let two_caps = filter("re:^[A-Z]{2}$");
+        assert!(two_caps.matches("ZZ"));
+        assert!(!two_caps.matches("ZZZ"));
+        assert!(filter("re:[0-9a-f]{32}").matches("hash:d41d8cd98f00b204e9800998ecf8427e"));
+
+        let url = filter("glob:*http*");
+        assert!(url.matches("https://example"));
+        assert!(!url.matches("htp"));
+        assert_eq!(url.to_string(), "glob:*http*");
+    }
+
+    #[test]
+    fn test_invalid_filters() {
+        let err = Filter::try_from(String::from("re:[a-z")).unwrap_err();
+        assert_eq!(
+            err,
+            "filter `re:[a-z` is not a valid regex: unclosed character class"
+        );
+        let err = Filter::try_from(String::from("glob:[a-z")).unwrap_err();
+        assert!(err.contains("filter `glob:[a-z` is not a valid glob"));
+    }
+
+    #[test]
+    fn test_filter_ignore_case() {
+        let exact = filter_with("fitbit", true, None);
+        assert!(exact.matches("Fitbit"));
+        assert!(exact.matches("FITBIT"));
+        assert!(!filter("fitbit").matches("Fitbit"));
+
+        assert!(filter_with("re:^zz$", true, None).matches("ZZ"));
+        assert!(filter_with("glob:HTTP*", true, None).matches("https://example"));
+        assert_eq!(
+            filter_with("fitbit", true, None).to_string(),
+            "fitbit (ignore case)"
+        );
+    }
+
+    #[test]
+    fn test_filter_normalization() {
+        // Precomposed é vs e + combining acute accent
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        assert!(!filter(composed).matches(decomposed));
+        assert!(filter_with(composed, false, Some(Normalization::Nfc)).matches(decomposed));
+        assert!(filter_with(decomposed, false, Some(Normalization::Nfc)).matches(composed));
+
+        // Ligature only folds with compatibility normalization
+        assert!(!filter_with("file", false, Some(Normalization::Nfc)).matches("\u{fb01}le"));
+        assert!(filter_with("file", false, Some(Normalization::Nfkc)).matches("\u{fb01}le"));
+        assert!(filter_with("re:^file$", false, Some(Normalization::Nfkc)).matches("\u{fb01}le"));
+    }
+
+    #[test]
+    fn test_canonical_casing() {
+        let canonical = Canonical::new(String::from("FitBit"));
+        assert!(!canonical.matches("FitBit"));
+        assert!(canonical.matches("Fitbit"));
+        assert!(canonical.matches("fitbit"));
+        assert!(canonical.matches("FITBIT"));
+        assert!(!canonical.matches("FitBits"));
+    }
+}
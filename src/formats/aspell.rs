@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::{text, DictionaryFormat, FormatError, Metadata, Parsed};
+
+/// First word of the header line of an aspell personal word list
+const HEADER_PRELUDE: &str = "personal_ws-1.1";
+
+/// Language written to the header when neither the config nor the file has one
+pub const DEFAULT_LANG: &str = "en";
+
+/// Reads the words and language tag from an aspell personal word list.
+///
+/// The header is `personal_ws-1.1 <lang> <count> [encoding]`. The count is not trusted (aspell doesn't either);
+/// every other non-empty line is a word.
+pub fn parse(content: &str) -> (HashSet<String>, Option<String>) {
+    let mut lang = None;
+    let mut words = HashSet::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if i == 0 && line.starts_with(HEADER_PRELUDE) {
+            lang = line.split_whitespace().nth(1).map(String::from);
+            continue;
+        }
+        if !line.is_empty() {
+            words.insert(line.to_string());
+        }
+    }
+    if lang.is_none() {
+        warn!(
+            "No `{}` header; aspell will not load this list until it is rewritten",
+            HEADER_PRELUDE
+        );
+    }
+    (words, lang)
+}
+
+/// Renders words as an aspell personal word list with a fresh header
+pub fn render(words: &[&String], lang: &str) -> String {
+    let mut content = format!("{} {} {} utf-8\n", HEADER_PRELUDE, lang, words.len());
+    for word in words {
+        content.push_str(word);
+        content.push('\n');
+    }
+    content
+}
+
+/// Aspell personal word lists
+#[derive(Debug)]
+pub struct Aspell;
+
+impl DictionaryFormat for Aspell {
+    fn parse(&self, _path: &Path, bytes: &[u8]) -> Result<Parsed, FormatError> {
+        let (content, layout) = text(bytes)?;
+        let (words, lang) = parse(&content);
+        Ok(Parsed {
+            words,
+            metadata: Metadata {
+                lang,
+                layout,
+                ..Metadata::default()
+            },
+        })
+    }
+
+    fn serialize(
+        &self,
+        _path: &Path,
+        words: &[&String],
+        metadata: &Metadata,
+        _existing: &[u8],
+    ) -> Result<Vec<u8>, FormatError> {
+        let lang = metadata.lang.as_deref().unwrap_or(DEFAULT_LANG);
+        Ok(metadata.layout.encode(&render(words, lang)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let (words, lang) = parse("personal_ws-1.1 de 99\nStraße\nObsidian\n\n");
+        assert_eq!(lang.as_deref(), Some("de"));
+        assert_eq!(
+            words,
+            HashSet::from([String::from("Straße"), String::from("Obsidian")])
+        );
+
+        let (words, lang) = parse("Obsidian\n");
+        assert_eq!(lang, None);
+        assert_eq!(words.len(), 1);
+    }
+
+    #[test]
+    fn test_render_regenerates_count() {
+        let a = String::from("alpha");
+        let b = String::from("beta");
+        let content = render(&[&a, &b], "en_GB");
+        assert_eq!(content, "personal_ws-1.1 en_GB 2 utf-8\nalpha\nbeta\n");
+        assert_eq!(
+            parse(&content),
+            (HashSet::from([a, b]), Some(String::from("en_GB")))
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::{text, DictionaryFormat, FormatError, Metadata, Parsed, TextLayout};
+use crate::user_dictionary::ChecksumStatus;
+
+/// Start of the line that holds the checksum of the words
+pub const CHECKSUM_PRELUDE: &str = "checksum_v1 = ";
+
+/// The checksummed word list Chromium (and so Obsidian) reads; the default format
+#[derive(Debug)]
+pub struct Chromium;
+
+impl DictionaryFormat for Chromium {
+    fn parse(&self, _path: &Path, bytes: &[u8]) -> Result<Parsed, FormatError> {
+        let (content, layout) = text(bytes)?;
+        let (words, checksum, stored_checksum) = parse(&content, &layout);
+        Ok(Parsed {
+            words,
+            metadata: Metadata {
+                checksum,
+                stored_checksum,
+                layout,
+                ..Metadata::default()
+            },
+        })
+    }
+
+    fn serialize(
+        &self,
+        _path: &Path,
+        words: &[&String],
+        metadata: &Metadata,
+        _existing: &[u8],
+    ) -> Result<Vec<u8>, FormatError> {
+        Ok(render(words, &metadata.layout))
+    }
+}
+
+/// Splits dictionary file content into the words, the state of the checksum line and the digest on it (if there is
+/// exactly one). `content` has already been decoded from a file laid out as `layout`.
+pub fn parse(
+    content: &str,
+    layout: &TextLayout,
+) -> (HashSet<String>, ChecksumStatus, Option<String>) {
+    // We care about every line other than the hash line.
+    // A valid dictionary will have the file hash on the last line but we want to tolerate
+    // dictionary files that might have the hash line anywhere.
+    let (checksum_lines, word_lines): (Vec<&str>, Vec<&str>) = content
+        .lines()
+        .partition(|line| line.contains(CHECKSUM_PRELUDE));
+    let words: HashSet<String> = word_lines.into_iter().map(String::from).collect();
+
+    let stored = match checksum_lines.as_slice() {
+        // Anything after the prelude is the digest
+        [line] => line
+            .split_once(CHECKSUM_PRELUDE)
+            .map(|(_, digest)| digest.trim().to_string()),
+        _ => None,
+    };
+    let checksum = match (checksum_lines.as_slice(), &stored) {
+        ([], _) => ChecksumStatus::Missing,
+        ([_], Some(stored)) => {
+            let mut sorted = words.iter().collect::<Vec<_>>();
+            sorted.sort();
+            let expected = checksum(&sorted, layout);
+            if *stored == expected {
+                ChecksumStatus::Valid
+            } else {
+                ChecksumStatus::Mismatched {
+                    stored: stored.to_string(),
+                    expected,
+                }
+            }
+        }
+        (lines, _) => ChecksumStatus::Multiple(lines.len()),
+    };
+
+    (words, checksum, stored)
+}
+
+/// Renders words as a Chromium dictionary file: one word per line then the checksum line
+pub fn render(words: &[&String], layout: &TextLayout) -> Vec<u8> {
+    let mut content = laid_out(words, layout);
+    // Checksum line is last line, should not have a newline
+    content
+        .extend_from_slice(format!("{}{}", CHECKSUM_PRELUDE, checksum(words, layout)).as_bytes());
+    content
+}
+
+/// Digest on the checksum line of a file with `words` (sorted) laid out as `layout`.
+/// Chromium checks the digest against the bytes before the checksum line exactly as they are in the file, so with
+/// CRLF or a BOM the digest differs from `calculate_hash_digest` (which is the plain `\n` layout).
+pub fn checksum(words: &[&String], layout: &TextLayout) -> String {
+    format!("{:?}", md5::compute(laid_out(words, layout)))
+}
+
+/// Bytes of one word per line laid out as `layout`; everything in the file before the checksum line
+fn laid_out(words: &[&String], layout: &TextLayout) -> Vec<u8> {
+    let mut content = String::new();
+    for word in words {
+        content.push_str(word);
+        content.push('\n');
+    }
+    layout.encode(&content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::LineEnding;
+    use crate::user_dictionary::calculate_hash_digest;
+
+    #[test]
+    fn test_parse_checksum_status() {
+        let words: HashSet<String> = HashSet::from_iter(["alpha".to_string(), "beta".to_string()]);
+        let digest = format!("{:?}", calculate_hash_digest(&words));
+
+        let (parsed, status, stored) = parse(
+            &format!("alpha\nbeta\n{}{}", CHECKSUM_PRELUDE, digest),
+            &TextLayout::default(),
+        );
+        assert_eq!(parsed, words);
+        assert_eq!(status, ChecksumStatus::Valid);
+        assert_eq!(stored, Some(digest.clone()));
+
+        let (_, status, _) = parse(
+            &format!("alpha\nbeta\nextra\n{}{}", CHECKSUM_PRELUDE, digest),
+            &TextLayout::default(),
+        );
+        assert!(matches!(status, ChecksumStatus::Mismatched { stored, .. } if stored == digest));
+
+        let (parsed, status, stored) = parse("alpha\nbeta\n", &TextLayout::default());
+        assert_eq!(parsed, words);
+        assert_eq!(status, ChecksumStatus::Missing);
+        assert_eq!(stored, None);
+
+        let (parsed, status, _) = parse(
+            &format!(
+                "alpha\n{}{}\nbeta\n{}{}",
+                CHECKSUM_PRELUDE, digest, CHECKSUM_PRELUDE, digest
+            ),
+            &TextLayout::default(),
+        );
+        assert_eq!(parsed, words);
+        assert_eq!(status, ChecksumStatus::Multiple(2));
+    }
+
+    #[test]
+    fn test_trait_round_trip() {
+        let a = String::from("Obsidian");
+        let b = String::from("zeta");
+        let path = Path::new("Custom Dictionary.txt");
+        let bytes = Chromium
+            .serialize(path, &[&a, &b], &Metadata::default(), b"")
+            .unwrap();
+        let words = HashSet::from([a.clone(), b.clone()]);
+        // In the plain layout the digest is the one for the words alone
+        assert!(bytes.ends_with(format!("{:?}", calculate_hash_digest(&words)).as_bytes()));
+        let parsed = Chromium.parse(path, &bytes).unwrap();
+        assert_eq!(parsed.words, HashSet::from([a, b]));
+        assert_eq!(parsed.metadata.checksum, ChecksumStatus::Valid);
+
+        assert!(matches!(
+            Chromium.parse(path, b"caf\xe9\n"),
+            Err(FormatError::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_crlf_and_bom_checksum() {
+        let a = String::from("alpha");
+        let b = String::from("beta");
+        let layout = TextLayout {
+            line_ending: LineEnding::Crlf,
+            bom: true,
+        };
+        let bytes = render(&[&a, &b], &layout);
+        // The digest covers the bytes as written, which is what Chromium checks
+        let body = b"\xEF\xBB\xBFalpha\r\nbeta\r\n";
+        assert!(bytes.starts_with(body));
+        assert!(bytes.ends_with(format!("{:?}", md5::compute(body)).as_bytes()));
+
+        let path = Path::new("Custom Dictionary.txt");
+        let parsed = Chromium.parse(path, &bytes).unwrap();
+        assert_eq!(parsed.words, HashSet::from([a, b]));
+        assert_eq!(parsed.metadata.checksum, ChecksumStatus::Valid);
+        assert_eq!(parsed.metadata.layout, layout);
+    }
+}
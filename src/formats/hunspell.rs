@@ -0,0 +1,241 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::{text, DictionaryFormat, FormatError, Metadata, Parsed};
+
+/// Written next to a `.dic` that has no affix file so Hunspell will load it
+const MINIMAL_AFF: &str = "SET UTF-8\n";
+
+/// Reads the words from a Hunspell `.dic` file.
+///
+/// The word count header is optional (personal dictionaries don't have one). A number on the first line is only
+/// the header in a `dic_file` (where the count may be approximate) or if it is exactly the number of entries after
+/// it; otherwise, e.g. `2024` at the top of a personal dictionary, it is a word. Affix flags (`word/AB`) and
+/// morphological fields after a tab are not part of the word (see `suffixes()` for how they survive a rewrite);
+/// `\/` is a literal slash.
+pub fn parse(content: &str, dic_file: bool) -> HashSet<String> {
+    entries(content, dic_file)
+        .map(|(word, _)| word)
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Each entry after the header split into the word and whatever follows it: affix flags and morphological fields
+fn entries(content: &str, dic_file: bool) -> impl Iterator<Item = (String, &str)> {
+    let mut lines: Vec<&str> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    if let Some(count) = lines
+        .first()
+        .and_then(|first| first.trim().parse::<usize>().ok())
+    {
+        if dic_file || count == lines.len() - 1 {
+            lines.remove(0);
+        }
+    }
+    lines.into_iter().map(|line| {
+        let line = line.trim_end();
+        let entry = line.split('\t').next().unwrap_or_default().trim_end();
+        let word = strip_flags(entry);
+        (unescape(word), &line[word.len()..])
+    })
+}
+
+/// The flags and morphological fields (`/MS`, `\tpo:noun`) of the entries that have them, keyed by the word.
+/// The words that are synced don't have them, so `render()` puts them back for words that still are in the file.
+pub fn suffixes(content: &str, dic_file: bool) -> HashMap<String, String> {
+    entries(content, dic_file)
+        .filter(|(word, suffix)| !word.is_empty() && !suffix.is_empty())
+        .map(|(word, suffix)| (word, suffix.to_string()))
+        .collect()
+}
+
+/// Renders words as a Hunspell `.dic` file with the word count header and the flags from `suffixes`
+pub fn render(words: &[&String], suffixes: &HashMap<String, String>) -> String {
+    let mut content = format!("{}\n", words.len());
+    for word in words {
+        content.push_str(&word.replace('/', "\\/"));
+        if let Some(suffix) = suffixes.get(*word) {
+            content.push_str(suffix);
+        }
+        content.push('\n');
+    }
+    content
+}
+
+/// True for a `.dic` file, as opposed to a personal dictionary such as `~/.hunspell_en_US`
+fn is_dic_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "dic")
+}
+
+/// Hunspell needs an affix file to go with every `.dic`; write a minimal one if there isn't one already
+pub fn write_companion_files(dic_path: &Path) -> io::Result<()> {
+    if !is_dic_file(dic_path) {
+        return Ok(());
+    }
+    let aff_path = dic_path.with_extension("aff");
+    if !aff_path.exists() {
+        debug!("Creating affix file '{}'", aff_path.display());
+        fs::write(&aff_path, MINIMAL_AFF)?;
+    }
+    Ok(())
+}
+
+/// Words are always read and written as UTF-8; warn if the affix file says the dictionary is in another encoding
+pub fn check_companion_files(dic_path: &Path) {
+    let aff_path = dic_path.with_extension("aff");
+    let Ok(aff) = fs::read_to_string(&aff_path) else {
+        return;
+    };
+    let encoding = aff
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("SET "))
+        .map(str::trim);
+    if let Some(encoding) = encoding.filter(|e| !e.eq_ignore_ascii_case("UTF-8")) {
+        warn!(
+            "'{}' declares `SET {}` but dictionaries are only read and written as UTF-8",
+            aff_path.display(),
+            encoding
+        );
+    }
+}
+
+/// Everything before the first `/` that isn't escaped with a `\`
+fn strip_flags(entry: &str) -> &str {
+    let mut escaped = false;
+    for (i, c) in entry.char_indices() {
+        match c {
+            '\\' => escaped = !escaped,
+            '/' if !escaped => return &entry[..i],
+            _ => escaped = false,
+        }
+    }
+    entry
+}
+
+fn unescape(word: &str) -> String {
+    word.replace("\\/", "/")
+}
+
+/// Hunspell `.dic` files
+#[derive(Debug)]
+pub struct Hunspell;
+
+impl DictionaryFormat for Hunspell {
+    fn parse(&self, path: &Path, bytes: &[u8]) -> Result<Parsed, FormatError> {
+        check_companion_files(path);
+        let (content, layout) = text(bytes)?;
+        Ok(Parsed {
+            words: parse(&content, is_dic_file(path)),
+            metadata: Metadata {
+                layout,
+                ..Metadata::default()
+            },
+        })
+    }
+
+    fn serialize(
+        &self,
+        path: &Path,
+        words: &[&String],
+        metadata: &Metadata,
+        existing: &[u8],
+    ) -> Result<Vec<u8>, FormatError> {
+        // A file that can't be read has no flags worth keeping
+        let suffixes = text(existing)
+            .map(|(content, _)| suffixes(&content, is_dic_file(path)))
+            .unwrap_or_default();
+        Ok(metadata.layout.encode(&render(words, &suffixes)))
+    }
+
+    fn after_write(&self, path: &Path) -> io::Result<()> {
+        write_companion_files(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let words = parse("4\nObsidian\nFitBit/MS\nand\\/or\nhello\tpo:noun\n", true);
+        let expected: HashSet<String> = ["Obsidian", "FitBit", "and/or", "hello"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        assert_eq!(words, expected);
+
+        // Personal dictionaries have no count header
+        assert_eq!(parse("42nd\n", false).len(), 1);
+        assert!(parse("", false).is_empty());
+    }
+
+    #[test]
+    fn test_parse_numeric_first_line() {
+        // A year at the top of a personal dictionary is a word
+        let words = parse("2024\nObsidian\n", false);
+        assert_eq!(
+            words,
+            HashSet::from(["2024".to_string(), "Obsidian".to_string()])
+        );
+        // Unless it is the number of entries after it, which is what `render()` writes
+        assert_eq!(parse("1\nObsidian\n", false).len(), 1);
+        // Hunspell only says the count in a `.dic` is approximate
+        assert_eq!(parse("3000\nObsidian\n", true).len(), 1);
+        assert!(is_dic_file(Path::new("en_US.dic")));
+        assert!(!is_dic_file(Path::new(".hunspell_en_US")));
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let a = String::from("Obsidian");
+        let b = String::from("and/or");
+        let content = render(&[&a, &b], &HashMap::new());
+        assert_eq!(content, "2\nObsidian\nand\\/or\n");
+        assert_eq!(parse(&content, false), HashSet::from([a, b]));
+    }
+
+    #[test]
+    fn test_rewrite_keeps_flags() {
+        let path = Path::new("en_US.dic");
+        let existing = b"3\nFitBit/MS\nand\\/or/X\thello\nhello\tpo:noun\n";
+        let parsed = Hunspell.parse(path, existing).unwrap();
+        assert_eq!(
+            parsed.words,
+            HashSet::from(["FitBit", "and/or", "hello"].map(String::from))
+        );
+
+        // `hello` is gone and `new` has no flags to keep
+        let words = ["FitBit", "and/or", "new"].map(String::from);
+        let bytes = Hunspell
+            .serialize(
+                path,
+                &words.iter().collect::<Vec<_>>(),
+                &parsed.metadata,
+                existing,
+            )
+            .unwrap();
+        assert_eq!(bytes, b"3\nFitBit/MS\nand\\/or/X\thello\nnew\n");
+    }
+
+    #[test]
+    fn test_companion_aff() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let dic = temp_dir.path().join("en_US.dic");
+        write_companion_files(&dic)?;
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("en_US.aff"))?,
+            MINIMAL_AFF
+        );
+
+        // Existing affix file is left alone
+        fs::write(temp_dir.path().join("en_US.aff"), "SET UTF-8\nTRY abc\n")?;
+        write_companion_files(&dic)?;
+        assert!(fs::read_to_string(temp_dir.path().join("en_US.aff"))?.contains("TRY"));
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::{text, DictionaryFormat, FormatError, Metadata, Parsed};
+
+/// JetBrains IDEs indent their XML with two spaces
+const INDENT: &str = "  ";
+
+/// An opening (or self closing) tag and the byte range it takes up in the file
+struct Tag {
+    start: usize,
+    end: usize,
+    self_closing: bool,
+}
+
+/// Reads the words from a JetBrains project dictionary, e.g. `.idea/dictionaries/<user>.xml`:
+/// `<component name="ProjectDictionaryState"><dictionary name="..."><words><w>word</w>...`.
+/// A file without a `<words>` element has no words. Tags inside comments (`<!-- <w>old</w> -->`) don't count and
+/// the text of a CDATA section (`<w><![CDATA[AT&T]]></w>`) is taken as it is.
+pub fn parse(content: &str) -> Result<HashSet<String>, String> {
+    let masked = mask_markup(content);
+    let Some(words) = find_tag(&masked, "words", 0) else {
+        return Ok(HashSet::new());
+    };
+    if words.self_closing {
+        return Ok(HashSet::new());
+    }
+    let close = find_close(&masked, "words", words.end)?;
+
+    let mut found = HashSet::new();
+    let mut from = words.end;
+    while let Some(w) = find_tag(&masked[..close], "w", from) {
+        if w.self_closing {
+            from = w.end;
+            continue;
+        }
+        let end = find_close(&masked[..close], "w", w.end)?;
+        let word = text_content(&content[w.end..end]).trim().to_string();
+        if !word.is_empty() {
+            found.insert(word);
+        }
+        from = end;
+    }
+    Ok(found)
+}
+
+/// Returns `content` with the `<words>` element replaced by `words`. Everything else in the file is left as it was;
+/// an empty file gets a new dictionary named `name`.
+pub fn render(content: &str, name: &str, words: &[&String]) -> Result<String, String> {
+    if content.trim().is_empty() {
+        return Ok(format!(
+            "<component name=\"ProjectDictionaryState\">\n{i}<dictionary name=\"{}\">\n{i}{i}{}\n{i}</dictionary>\n</component>\n",
+            escape(name),
+            render_words(words, &INDENT.repeat(2)),
+            i = INDENT,
+        ));
+    }
+
+    // Same as parsing; a commented out `<words>` is not the one to replace
+    let masked = mask_markup(content);
+    let (range, replacement) = match find_tag(&masked, "words", 0) {
+        Some(tag) => {
+            let indent = line_indent(content, tag.start);
+            let end = if tag.self_closing {
+                tag.end
+            } else {
+                let close = find_close(&masked, "words", tag.end)?;
+                close + "</words>".len()
+            };
+            (tag.start..end, render_words(words, indent))
+        }
+        None => {
+            let dictionary = find_tag(&masked, "dictionary", 0)
+                .ok_or_else(|| String::from("no `<dictionary>` element to put the words in"))?;
+            let indent = line_indent(content, dictionary.start);
+            let words_indent = format!("{}{}", indent, INDENT);
+            let element = render_words(words, &words_indent);
+            if dictionary.self_closing {
+                // `<dictionary name="x" />` => `<dictionary name="x">...</dictionary>`
+                let open = content[dictionary.start..dictionary.end - 2].trim_end();
+                (
+                    dictionary.start..dictionary.end,
+                    format!(
+                        "{}>\n{}{}\n{}</dictionary>",
+                        open, words_indent, element, indent
+                    ),
+                )
+            } else {
+                let at = dictionary.end;
+                (at..at, format!("\n{}{}", words_indent, element))
+            }
+        }
+    };
+
+    let mut rendered = String::with_capacity(content.len() + replacement.len());
+    rendered.push_str(&content[..range.start]);
+    rendered.push_str(&replacement);
+    rendered.push_str(&content[range.end..]);
+    Ok(rendered)
+}
+
+/// `<words>` element with one `<w>` per word, starting at the current position on a line indented by `indent`
+fn render_words(words: &[&String], indent: &str) -> String {
+    if words.is_empty() {
+        return String::from("<words />");
+    }
+    let mut element = String::from("<words>\n");
+    for word in words {
+        element.push_str(&format!("{}{}<w>{}</w>\n", indent, INDENT, escape(word)));
+    }
+    element.push_str(indent);
+    element.push_str("</words>");
+    element
+}
+
+/// Finds the first `<name ...>` tag at or after `from`. `<names>` and the like don't count.
+fn find_tag(content: &str, name: &str, from: usize) -> Option<Tag> {
+    let open = format!("<{}", name);
+    let mut search = from;
+    while let Some(offset) = content[search..].find(&open) {
+        let start = search + offset;
+        let after = start + open.len();
+        match content[after..].chars().next() {
+            Some(c) if c == '>' || c == '/' || c.is_whitespace() => {
+                let end = after + content[after..].find('>')? + 1;
+                let self_closing = content[..end - 1].ends_with('/');
+                return Some(Tag {
+                    start,
+                    end,
+                    self_closing,
+                });
+            }
+            _ => search = after,
+        }
+    }
+    None
+}
+
+/// Start of the `</name>` that closes an element whose content starts at `from`
+fn find_close(content: &str, name: &str, from: usize) -> Result<usize, String> {
+    content[from..]
+        .find(&format!("</{}>", name))
+        .map(|offset| from + offset)
+        .ok_or_else(|| format!("`<{}>` is never closed", name))
+}
+
+/// Comments and CDATA sections: how each starts and ends
+const MARKUP: [(&str, &str); 2] = [("<!--", "-->"), ("<![CDATA[", "]]>")];
+
+/// The first comment or CDATA section in `content`: where it starts, how long it is (to the end of `content` if it
+/// is never closed) and whether it is CDATA
+fn next_markup(content: &str) -> Option<(usize, usize, bool)> {
+    let (at, open, close) = MARKUP
+        .iter()
+        .filter_map(|(open, close)| content.find(open).map(|at| (at, *open, *close)))
+        .min_by_key(|(at, _, _)| *at)?;
+    let len = content[at..]
+        .find(close)
+        .map_or(content.len() - at, |end| end + close.len());
+    Some((at, len, open != "<!--"))
+}
+
+/// `content` with every comment and CDATA section blanked out. It is the same length so a position in one is the
+/// same position in the other; tags are looked for in this and text is taken from `content`.
+fn mask_markup(content: &str) -> String {
+    let mut masked = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some((at, len, _)) = next_markup(rest) {
+        masked.push_str(&rest[..at]);
+        for c in rest[at..at + len].chars() {
+            masked.push_str(&" ".repeat(c.len_utf8()));
+        }
+        rest = &rest[at + len..];
+    }
+    masked.push_str(rest);
+    masked
+}
+
+/// Text of an element: entities decoded, CDATA sections taken as they are and comments left out
+fn text_content(content: &str) -> String {
+    let mut text = String::new();
+    let mut rest = content;
+    while let Some((at, len, cdata)) = next_markup(rest) {
+        text.push_str(&unescape(&rest[..at]));
+        if cdata {
+            let section = &rest[at + "<![CDATA[".len()..at + len];
+            text.push_str(section.strip_suffix("]]>").unwrap_or(section));
+        }
+        rest = &rest[at + len..];
+    }
+    text.push_str(&unescape(rest));
+    text
+}
+
+/// Leading whitespace of the line `pos` is on
+fn line_indent(content: &str, pos: usize) -> &str {
+    let line_start = content[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let line = &content[line_start..];
+    let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+    &line[..indent_len]
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Replaces the predefined entities and character references; anything else is left as it is
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        unescaped.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest.find(';').map(|semi| (&rest[1..semi], semi));
+        let decoded = entity.and_then(|(entity, semi)| {
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity
+                    .strip_prefix("#x")
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, semi))
+        });
+        match decoded {
+            Some((c, semi)) => {
+                unescaped.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// JetBrains IDE project dictionaries
+#[derive(Debug)]
+pub struct JetBrains;
+
+impl DictionaryFormat for JetBrains {
+    fn parse(&self, _path: &Path, bytes: &[u8]) -> Result<Parsed, FormatError> {
+        let (content, layout) = text(bytes)?;
+        let words = parse(&content).map_err(FormatError::Invalid)?;
+        Ok(Parsed {
+            words,
+            metadata: Metadata {
+                layout,
+                ..Metadata::default()
+            },
+        })
+    }
+
+    fn serialize(
+        &self,
+        path: &Path,
+        words: &[&String],
+        metadata: &Metadata,
+        existing: &[u8],
+    ) -> Result<Vec<u8>, FormatError> {
+        // The IDE names the file after the dictionary
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        let rendered = render(&text(existing)?.0, &name, words).map_err(FormatError::Invalid)?;
+        Ok(metadata.layout.encode(&rendered))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::words;
+
+    const PROJECT_DICTIONARY: &str = r#"<component name="ProjectDictionaryState">
+  <dictionary name="alice">
+    <words>
+      <w>obsidian</w>
+      <w>AT&amp;T</w>
+      <w>caf&#233;</w>
+    </words>
+  </dictionary>
+</component>"#;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse(PROJECT_DICTIONARY).unwrap(),
+            words(&["obsidian", "AT&T", "café"])
+        );
+        assert!(parse(r#"<component><dictionary name="a" /></component>"#)
+            .unwrap()
+            .is_empty());
+        assert!(parse("<words><w>open").is_err());
+    }
+
+    #[test]
+    fn test_parse_skips_comments_and_reads_cdata() {
+        let content = r#"<component name="ProjectDictionaryState">
+  <!-- <words><w>decoy</w></words> -->
+  <dictionary name="alice">
+    <words>
+      <!-- <w>removed</w> -->
+      <w>obsidian</w>
+      <w><![CDATA[AT&T <Inc>]]></w>
+      <w>caf<!-- accent -->&#233;</w>
+    </words>
+  </dictionary>
+</component>"#;
+        assert_eq!(
+            parse(content).unwrap(),
+            words(&["obsidian", "AT&T <Inc>", "café"])
+        );
+        // A comment that never ends hides the rest of the file
+        assert!(parse("<words><!-- <w>a</w></words>").is_err());
+
+        // The commented out `<words>` is left alone and the real one replaced
+        let a = String::from("alpha");
+        let rendered = render(content, "alice", &[&a]).unwrap();
+        assert!(rendered.contains("<!-- <words><w>decoy</w></words> -->"));
+        assert!(rendered.contains("    <words>\n      <w>alpha</w>\n    </words>\n"));
+        assert_eq!(parse(&rendered).unwrap(), words(&["alpha"]));
+    }
+
+    #[test]
+    fn test_render_escapes_and_round_trips() {
+        let a = String::from("<tag>");
+        let b = String::from("AT&T");
+        let rendered = render(PROJECT_DICTIONARY, "alice", &[&a, &b]).unwrap();
+        assert_eq!(
+            rendered,
+            "<component name=\"ProjectDictionaryState\">\n  <dictionary name=\"alice\">\n    <words>\n      <w>&lt;tag&gt;</w>\n      <w>AT&amp;T</w>\n    </words>\n  </dictionary>\n</component>"
+        );
+        assert_eq!(parse(&rendered).unwrap(), words(&["<tag>", "AT&T"]));
+    }
+
+    #[test]
+    fn test_render_adds_words() {
+        let a = String::from("obsidian");
+        let expected = "<component name=\"ProjectDictionaryState\">\n  <dictionary name=\"alice\">\n    <words>\n      <w>obsidian</w>\n    </words>\n  </dictionary>\n</component>\n";
+        assert_eq!(render("", "alice", &[&a]).unwrap(), expected);
+        assert_eq!(
+            render(
+                "<component name=\"ProjectDictionaryState\">\n  <dictionary name=\"alice\" />\n</component>\n",
+                "alice",
+                &[&a]
+            )
+            .unwrap(),
+            expected
+        );
+        assert!(render("<component />", "alice", &[&a]).is_err());
+    }
+}
@@ -0,0 +1,684 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use super::{text, DictionaryFormat, FormatError, Metadata, Parsed};
+
+/// Indent used when the file doesn't give us anything better to go on
+const DEFAULT_INDENT: &str = "  ";
+
+/// A JSON value and the byte range it takes up in the file
+#[derive(Debug)]
+enum Node {
+    Object {
+        members: Vec<Member>,
+        start: usize,
+        end: usize,
+    },
+    Array {
+        items: Vec<Node>,
+        start: usize,
+        end: usize,
+    },
+    String {
+        value: String,
+        start: usize,
+        end: usize,
+    },
+    /// Numbers, booleans and null; never looked into
+    Other { start: usize, end: usize },
+}
+
+#[derive(Debug)]
+struct Member {
+    key: String,
+    key_start: usize,
+    value: Node,
+}
+
+impl Node {
+    fn start(&self) -> usize {
+        match self {
+            Node::Object { start, .. }
+            | Node::Array { start, .. }
+            | Node::String { start, .. }
+            | Node::Other { start, .. } => *start,
+        }
+    }
+
+    fn end(&self) -> usize {
+        match self {
+            Node::Object { end, .. }
+            | Node::Array { end, .. }
+            | Node::String { end, .. }
+            | Node::Other { end, .. } => *end,
+        }
+    }
+}
+
+/// The comments inside an array, so they survive it being rewritten
+#[derive(Debug, Default)]
+struct ArrayComments {
+    /// Comments on the lines before a word and after it on its own line, keyed by the word
+    words: HashMap<String, (Vec<String>, Option<String>)>,
+    /// Comments after the last item
+    closing: Vec<String>,
+}
+
+impl ArrayComments {
+    /// Comments are tied to the word they sit on or above; they move with it when the words are sorted and go
+    /// when it is removed. Comments after the last item stay at the end of the array.
+    fn new(content: &str, items: &[Node], start: usize, end: usize) -> Self {
+        let mut comments = ArrayComments::default();
+        let mut leading = Vec::new();
+        let mut previous: Option<&Node> = None;
+        // After the `[` and before the `]`
+        let mut gap_start = start + 1;
+        for item in items.iter().map(Some).chain([None]) {
+            let gap_end = item.map_or(end - 1, Node::start);
+            for (comment, same_line) in scan_comments(&content[gap_start..gap_end]) {
+                match previous {
+                    Some(Node::String { value, .. }) if same_line => {
+                        comments.words.entry(value.clone()).or_default().1 = Some(comment);
+                    }
+                    _ => leading.push(comment),
+                }
+            }
+            match item {
+                Some(Node::String { value, .. }) if !leading.is_empty() => {
+                    comments.words.entry(value.clone()).or_default().0 =
+                        std::mem::take(&mut leading);
+                }
+                Some(_) => {}
+                None => comments.closing = std::mem::take(&mut leading),
+            }
+            if let Some(item) = item {
+                gap_start = item.end();
+            }
+            previous = item;
+        }
+        comments
+    }
+}
+
+/// The comments in whitespace (and commas) between two values, and whether each is on the line the gap starts on
+fn scan_comments(gap: &str) -> Vec<(String, bool)> {
+    let mut comments = Vec::new();
+    let mut same_line = true;
+    let mut rest = gap;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("//") {
+            let len = rest.find('\n').unwrap_or(rest.len());
+            comments.push((rest[..len].trim_end().to_string(), same_line));
+            rest = &rest[len..];
+        } else if rest.starts_with("/*") {
+            let len = rest.find("*/").map_or(rest.len(), |i| i + 2);
+            comments.push((rest[..len].to_string(), same_line));
+            rest = &rest[len..];
+        } else {
+            if c == '\n' {
+                same_line = false;
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    comments
+}
+
+/// Where the key path led
+enum Lookup<'a> {
+    Found(&'a Node),
+    /// Deepest object on the path and the part of the key path that isn't in it
+    Missing {
+        object: &'a Node,
+        remaining: String,
+    },
+}
+
+/// Reads the words from the array at `key` in a JSON or JSONC file.
+///
+/// `key` is a dotted path. At each level the longest run of segments that is a key wins, so `cSpell.words` finds
+/// both VS Code's flat `"cSpell.words"` setting and a nested `{"cSpell": {"words": []}}`. A missing key or empty
+/// file has no words; anything else in the array that isn't a string is ignored.
+pub fn parse(content: &str, key: &str) -> Result<HashSet<String>, String> {
+    if content.trim().is_empty() {
+        return Ok(HashSet::new());
+    }
+    let root = Parser::new(content).parse_document()?;
+    match lookup(&root, key)? {
+        Lookup::Missing { .. } => {
+            debug!("No `{}` in the file yet", key);
+            Ok(HashSet::new())
+        }
+        Lookup::Found(Node::Array { items, .. }) => Ok(items
+            .iter()
+            .filter_map(|item| match item {
+                Node::String { value, .. } => Some(value.clone()),
+                _ => {
+                    warn!("Ignoring `{}` entry that is not a string", key);
+                    None
+                }
+            })
+            .collect()),
+        Lookup::Found(node) => Err(format!(
+            "`{}` (line {}) is not an array",
+            key,
+            line_number(content, node.start())
+        )),
+    }
+}
+
+/// Returns `content` with the array at `key` replaced by `words`, or added if there is none.
+/// Everything else in the file, comments included, is left exactly as it was; comments inside the array are kept
+/// with their words (see `ArrayComments`).
+pub fn render(content: &str, key: &str, words: &[&String]) -> Result<String, String> {
+    if content.trim().is_empty() {
+        return Ok(format!(
+            "{{\n{}{}: {}\n}}\n",
+            DEFAULT_INDENT,
+            quote(key),
+            render_array(
+                words,
+                &DEFAULT_INDENT.repeat(2),
+                DEFAULT_INDENT,
+                &ArrayComments::default()
+            )
+        ));
+    }
+
+    let root = Parser::new(content).parse_document()?;
+    let unit = indent_unit(content, &root);
+    let (range, replacement) = match lookup(&root, key)? {
+        Lookup::Found(Node::Array { items, start, end }) => {
+            let close_indent = line_indent(content, *start);
+            // Keep the indentation the array already has if it is spread over several lines
+            let item_indent = match items.first() {
+                Some(first) if content[*start..first.start()].contains('\n') => {
+                    line_indent(content, first.start()).to_string()
+                }
+                _ => format!("{}{}", close_indent, unit),
+            };
+            let comments = ArrayComments::new(content, items, *start, *end);
+            (
+                *start..*end,
+                render_array(words, &item_indent, close_indent, &comments),
+            )
+        }
+        Lookup::Found(node) => {
+            return Err(format!(
+                "`{}` (line {}) is not an array",
+                key,
+                line_number(content, node.start())
+            ))
+        }
+        Lookup::Missing {
+            object: Node::Object { members, start, .. },
+            remaining,
+        } => {
+            let member = |indent: &str| {
+                format!(
+                    "{}: {}",
+                    quote(&remaining),
+                    render_array(
+                        words,
+                        &format!("{}{}", indent, unit),
+                        indent,
+                        &ArrayComments::default(),
+                    )
+                )
+            };
+            match members.last() {
+                // Goes right after the last member so it can never end up on the wrong side of a comma
+                Some(last) => {
+                    let indent = line_indent(content, last.key_start);
+                    let at = last.value.end();
+                    (at..at, format!(",\n{}{}", indent, member(indent)))
+                }
+                None => {
+                    let object_indent = line_indent(content, *start);
+                    let indent = format!("{}{}", object_indent, unit);
+                    let at = start + 1;
+                    (
+                        at..at,
+                        format!("\n{}{}\n{}", indent, member(&indent), object_indent),
+                    )
+                }
+            }
+        }
+        Lookup::Missing { .. } => unreachable!("lookup only stops at objects"),
+    };
+
+    let mut rendered = String::with_capacity(content.len() + replacement.len());
+    rendered.push_str(&content[..range.start]);
+    rendered.push_str(&replacement);
+    rendered.push_str(&content[range.end..]);
+    Ok(rendered)
+}
+
+fn lookup<'a>(root: &'a Node, key: &str) -> Result<Lookup<'a>, String> {
+    let segments: Vec<&str> = key.split('.').collect();
+    let mut node = root;
+    let mut rest = &segments[..];
+    loop {
+        let Node::Object { members, .. } = node else {
+            return Err(format!(
+                "`{}` is not an object",
+                segments[..segments.len() - rest.len()].join(".")
+            ));
+        };
+        let found = (1..=rest.len()).rev().find_map(|n| {
+            let candidate = rest[..n].join(".");
+            members
+                .iter()
+                .find(|member| member.key == candidate)
+                .map(|member| (n, &member.value))
+        });
+        match found {
+            Some((n, value)) if n == rest.len() => return Ok(Lookup::Found(value)),
+            Some((n, value)) => {
+                node = value;
+                rest = &rest[n..];
+            }
+            None => {
+                return Ok(Lookup::Missing {
+                    object: node,
+                    remaining: rest.join("."),
+                })
+            }
+        }
+    }
+}
+
+fn render_array(
+    words: &[&String],
+    item_indent: &str,
+    close_indent: &str,
+    comments: &ArrayComments,
+) -> String {
+    if words.is_empty() && comments.closing.is_empty() {
+        return String::from("[]");
+    }
+    let mut lines = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        let (leading, trailing) = comments
+            .words
+            .get(*word)
+            .map_or((&[][..], None), |(leading, trailing)| {
+                (leading.as_slice(), trailing.as_ref())
+            });
+        for comment in leading {
+            lines.push(format!("{}{}", item_indent, comment));
+        }
+        let comma = if i + 1 < words.len() { "," } else { "" };
+        let mut line = format!("{}{}{}", item_indent, quote(word), comma);
+        if let Some(trailing) = trailing {
+            line.push(' ');
+            line.push_str(trailing);
+        }
+        lines.push(line);
+    }
+    for comment in &comments.closing {
+        lines.push(format!("{}{}", item_indent, comment));
+    }
+    format!("[\n{}\n{}]", lines.join("\n"), close_indent)
+}
+
+fn quote(text: &str) -> String {
+    serde_json::Value::String(text.to_string()).to_string()
+}
+
+/// Indentation of the top level members, if they are on lines of their own
+fn indent_unit<'a>(content: &'a str, root: &Node) -> &'a str {
+    match root {
+        Node::Object { members, start, .. } => match members.first() {
+            Some(first) if content[*start..first.key_start].contains('\n') => {
+                let indent = line_indent(content, first.key_start);
+                if indent.is_empty() {
+                    DEFAULT_INDENT
+                } else {
+                    indent
+                }
+            }
+            _ => DEFAULT_INDENT,
+        },
+        _ => DEFAULT_INDENT,
+    }
+}
+
+/// Leading whitespace of the line `pos` is on
+fn line_indent(content: &str, pos: usize) -> &str {
+    let line_start = content[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let line = &content[line_start..];
+    let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+    &line[..indent_len]
+}
+
+fn line_number(content: &str, pos: usize) -> usize {
+    content[..pos].matches('\n').count() + 1
+}
+
+/// Just enough of a JSON parser to find values and where they are. Comments and trailing commas (JSONC, as used by
+/// VS Code) are allowed.
+struct Parser<'a> {
+    content: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(content: &'a str) -> Self {
+        Parser { content, pos: 0 }
+    }
+
+    fn parse_document(mut self) -> Result<Node, String> {
+        // Editors sometimes leave a BOM at the start
+        if self.content.starts_with('\u{feff}') {
+            self.pos = '\u{feff}'.len_utf8();
+        }
+        self.skip_trivia()?;
+        let root = self.parse_value()?;
+        self.skip_trivia()?;
+        if self.pos != self.content.len() {
+            return Err(self.error("unexpected content after the top level value"));
+        }
+        Ok(root)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.content.as_bytes().get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} (line {})", message, line_number(self.content, self.pos))
+    }
+
+    /// Whitespace and comments
+    fn skip_trivia(&mut self) -> Result<(), String> {
+        loop {
+            let rest = &self.content[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if let Some(comment) = trimmed.strip_prefix("/*") {
+                let end = comment
+                    .find("*/")
+                    .ok_or_else(|| self.error("unterminated comment"))?;
+                self.pos += end + 4;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected `{}`", byte as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> Result<Node, String> {
+        match self.peek() {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => self.parse_string(),
+            Some(_) => {
+                let start = self.pos;
+                let rest = &self.content[start..];
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || ",]}/".contains(c))
+                    .unwrap_or(rest.len());
+                if len == 0 {
+                    return Err(self.error("expected a value"));
+                }
+                self.pos += len;
+                Ok(Node::Other {
+                    start,
+                    end: self.pos,
+                })
+            }
+            None => Err(self.error("unexpected end of file")),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        self.expect(b'"')?;
+        let mut escaped = false;
+        for (i, c) in self.content[self.pos..].char_indices() {
+            match c {
+                '\\' => escaped = !escaped,
+                '"' if !escaped => {
+                    self.pos += i + 1;
+                    let value = serde_json::from_str(&self.content[start..self.pos])
+                        .map_err(|err| self.error(&err.to_string()))?;
+                    return Ok(Node::String {
+                        value,
+                        start,
+                        end: self.pos,
+                    });
+                }
+                _ => escaped = false,
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn parse_array(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some(b']') {
+                break;
+            }
+            items.push(self.parse_value()?);
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => break,
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+        self.pos += 1;
+        Ok(Node::Array {
+            items,
+            start,
+            end: self.pos,
+        })
+    }
+
+    fn parse_object(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some(b'}') {
+                break;
+            }
+            let key_start = self.pos;
+            let key = match self.parse_string()? {
+                Node::String { value, .. } => value,
+                _ => unreachable!("parse_string only returns strings"),
+            };
+            self.skip_trivia()?;
+            self.expect(b':')?;
+            self.skip_trivia()?;
+            let value = self.parse_value()?;
+            members.push(Member {
+                key,
+                key_start,
+                value,
+            });
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => break,
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+        self.pos += 1;
+        Ok(Node::Object {
+            members,
+            start,
+            end: self.pos,
+        })
+    }
+}
+
+/// An array of words inside a JSON or JSONC file
+#[derive(Debug)]
+pub struct Json {
+    /// Dotted path to the array, e.g. `cSpell.words`
+    pub key: String,
+}
+
+impl DictionaryFormat for Json {
+    fn parse(&self, _path: &Path, bytes: &[u8]) -> Result<Parsed, FormatError> {
+        let (content, layout) = text(bytes)?;
+        let words = parse(&content, &self.key).map_err(FormatError::Invalid)?;
+        Ok(Parsed {
+            words,
+            metadata: Metadata {
+                layout,
+                ..Metadata::default()
+            },
+        })
+    }
+
+    fn serialize(
+        &self,
+        _path: &Path,
+        words: &[&String],
+        metadata: &Metadata,
+        existing: &[u8],
+    ) -> Result<Vec<u8>, FormatError> {
+        // Only the words array is ours; everything else in the file is kept as is
+        let rendered =
+            render(&text(existing)?.0, &self.key, words).map_err(FormatError::Invalid)?;
+        Ok(metadata.layout.encode(&rendered))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::words;
+
+    const SETTINGS: &str = r#"{
+    // Editor
+    "editor.tabSize": 4,
+    "cSpell.words": [
+        "Obsidian",
+        "FitBit", /* trailing comma is fine */
+    ],
+}
+"#;
+
+    #[test]
+    fn test_parse_flat_and_nested_keys() {
+        assert_eq!(
+            parse(SETTINGS, "cSpell.words").unwrap(),
+            words(&["Obsidian", "FitBit"])
+        );
+        let nested = r#"{"cSpell": {"words": ["café", 1]}}"#;
+        assert_eq!(parse(nested, "cSpell.words").unwrap(), words(&["café"]));
+        assert!(parse(nested, "missing.words").unwrap().is_empty());
+        assert!(parse("", "words").unwrap().is_empty());
+
+        let err = parse(r#"{"words": "nope"}"#, "words").unwrap_err();
+        assert!(err.contains("not an array"), "{}", err);
+        assert!(parse("{\"words\": [}", "words").is_err());
+    }
+
+    #[test]
+    fn test_render_replaces_only_the_array() {
+        let a = String::from("alpha");
+        let b = String::from("beta");
+        let rendered = render(SETTINGS, "cSpell.words", &[&a, &b]).unwrap();
+        assert_eq!(
+            rendered,
+            "{\n    // Editor\n    \"editor.tabSize\": 4,\n    \"cSpell.words\": [\n        \"alpha\",\n        \"beta\"\n    ],\n}\n"
+        );
+        assert_eq!(
+            parse(&rendered, "cSpell.words").unwrap(),
+            words(&["alpha", "beta"])
+        );
+    }
+
+    #[test]
+    fn test_render_keeps_comments_in_the_array() {
+        let content = r#"{
+  "words": [ // sorted by hand
+    // brand names
+    "Obsidian", // the app
+    "FitBit",
+    /* typo, remove */ "teh",
+    "zeta", /* last */
+    // add new words above
+  ],
+}
+"#;
+        assert_eq!(
+            parse(content, "words").unwrap(),
+            words(&["Obsidian", "FitBit", "teh", "zeta"])
+        );
+        let words = ["FitBit", "Obsidian", "alpha", "zeta"].map(String::from);
+        let words: Vec<&String> = words.iter().collect();
+        // Comments move with their word; the ones of a removed word go with it
+        assert_eq!(
+            render(content, "words", &words).unwrap(),
+            r#"{
+  "words": [
+    "FitBit",
+    // sorted by hand
+    // brand names
+    "Obsidian", // the app
+    "alpha",
+    "zeta" /* last */
+    // add new words above
+  ],
+}
+"#
+        );
+        // Nothing left but the comments at the end
+        assert_eq!(
+            render(content, "words", &[]).unwrap(),
+            "{\n  \"words\": [\n    // add new words above\n  ],\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let content = "{\"a\": {\"b\": 1,},\n\"words\": [\"alpha\",\n],\n}";
+        assert_eq!(parse(content, "words").unwrap(), words(&["alpha"]));
+        let beta = String::from("beta");
+        // The comma after the array is kept; the one inside it is not needed
+        assert_eq!(
+            render(content, "words", &[&beta]).unwrap(),
+            "{\"a\": {\"b\": 1,},\n\"words\": [\n  \"beta\"\n],\n}"
+        );
+        assert!(parse("{\"words\": [,]}", "words").is_err());
+        assert!(parse("{,}", "words").is_err());
+    }
+
+    #[test]
+    fn test_render_adds_missing_key() {
+        let a = String::from("alpha");
+        let rendered = render("{\n  \"version\": \"0.2\"\n}\n", "words", &[&a]).unwrap();
+        assert_eq!(
+            rendered,
+            "{\n  \"version\": \"0.2\",\n  \"words\": [\n    \"alpha\"\n  ]\n}\n"
+        );
+        assert_eq!(
+            render("{}", "words", &[&a]).unwrap(),
+            "{\n  \"words\": [\n    \"alpha\"\n  ]\n}"
+        );
+        assert_eq!(
+            render("", "cSpell.words", &[]).unwrap(),
+            "{\n  \"cSpell.words\": []\n}\n"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use super::{text, DictionaryFormat, FormatError, Metadata, Parsed};
+
+/// First line of every LibreOffice (and OpenOffice) user dictionary
+const HEADER: &str = "OOoUserDict1";
+
+/// Line that ends the header
+const HEADER_END: &str = "---";
+
+/// `lang:` value for a dictionary that applies to every language
+pub const DEFAULT_LANG: &str = "<none>";
+
+/// Reads the words and language from a LibreOffice user dictionary such as `standard.dic`:
+///
+/// ```text
+/// OOoUserDict1
+/// lang: <none>
+/// type: positive
+/// ---
+/// word
+/// ```
+///
+/// `=` in a word marks where it may be hyphenated and is not part of the word (see `hyphenations()` for how the
+/// marks survive a rewrite). Negative dictionaries list words that are always wrong so they can't be synced.
+pub fn parse(content: &str) -> Result<(HashSet<String>, Option<String>), String> {
+    let mut lines = content.lines();
+    if lines.next().map(str::trim) != Some(HEADER) {
+        return Err(format!("missing the `{}` header", HEADER));
+    }
+
+    let mut lang = None;
+    for line in lines.by_ref() {
+        let line = line.trim();
+        if line == HEADER_END {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("lang:") {
+            lang = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("type:") {
+            if value.trim() != "positive" {
+                return Err(format!(
+                    "`type: {}` dictionary; only positive dictionaries hold words to sync",
+                    value.trim()
+                ));
+            }
+        }
+    }
+
+    let words = entries(lines)
+        .map(|entry| entry.replace('=', ""))
+        .filter(|word| !word.is_empty())
+        .collect();
+    Ok((words, lang))
+}
+
+/// The entries of the lines after the header, hyphenation marks and all
+fn entries<'a>(lines: impl Iterator<Item = &'a str>) -> impl Iterator<Item = &'a str> {
+    // Replacements in negative dictionaries come after `//`; harmless to strip here too
+    lines.map(|line| line.split("//").next().unwrap_or_default().trim())
+}
+
+/// The hyphenated entries (`hy=phen=ation`) of a dictionary file, keyed by the word without the marks.
+/// The words that are synced don't have the marks, so `render()` puts them back for words that still are in the file.
+pub fn hyphenations(content: &str) -> HashMap<String, String> {
+    let body = content
+        .lines()
+        .skip_while(|line| line.trim() != HEADER_END)
+        .skip(1);
+    entries(body)
+        .filter(|entry| entry.contains('='))
+        .map(|entry| (entry.replace('=', ""), entry.to_string()))
+        .collect()
+}
+
+/// Renders words as a positive LibreOffice user dictionary for `lang`, with the marks from `hyphenations`
+pub fn render(words: &[&String], lang: &str, hyphenations: &HashMap<String, String>) -> String {
+    let mut content = format!(
+        "{}\nlang: {}\ntype: positive\n{}\n",
+        HEADER, lang, HEADER_END
+    );
+    for word in words {
+        content.push_str(hyphenations.get(*word).unwrap_or(word));
+        content.push('\n');
+    }
+    content
+}
+
+/// LibreOffice user dictionaries
+#[derive(Debug)]
+pub struct LibreOffice;
+
+impl DictionaryFormat for LibreOffice {
+    fn parse(&self, _path: &Path, bytes: &[u8]) -> Result<Parsed, FormatError> {
+        let (content, layout) = text(bytes)?;
+        let (words, lang) = parse(&content).map_err(FormatError::Invalid)?;
+        Ok(Parsed {
+            words,
+            metadata: Metadata {
+                lang,
+                layout,
+                ..Metadata::default()
+            },
+        })
+    }
+
+    fn serialize(
+        &self,
+        _path: &Path,
+        words: &[&String],
+        metadata: &Metadata,
+        existing: &[u8],
+    ) -> Result<Vec<u8>, FormatError> {
+        let lang = metadata.lang.as_deref().unwrap_or(DEFAULT_LANG);
+        // A file that can't be read has no marks worth keeping
+        let hyphenations = text(existing)
+            .map(|(content, _)| hyphenations(&content))
+            .unwrap_or_default();
+        Ok(metadata.layout.encode(&render(words, lang, &hyphenations)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("fixtures/standard.dic");
+
+    #[test]
+    fn test_parse_fixture() {
+        let (words, lang) = parse(FIXTURE).unwrap();
+        assert_eq!(lang.as_deref(), Some(DEFAULT_LANG));
+        let expected: HashSet<String> = ["Obsidian", "hyphenation", "café"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        assert_eq!(words, expected);
+
+        let err = parse("OOoUserDict1\nlang: en-US\ntype: negative\n---\nteh//the\n").unwrap_err();
+        assert!(err.contains("negative"), "{}", err);
+        assert!(parse("Obsidian\n").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let a = String::from("Obsidian");
+        let content = render(&[&a], "en-US", &HashMap::new());
+        assert_eq!(
+            content,
+            "OOoUserDict1\nlang: en-US\ntype: positive\n---\nObsidian\n"
+        );
+        assert_eq!(
+            parse(&content).unwrap(),
+            (HashSet::from([a]), Some(String::from("en-US")))
+        );
+    }
+
+    #[test]
+    fn test_rewrite_keeps_hyphenation() {
+        let hyphenations = hyphenations(FIXTURE);
+        assert_eq!(
+            hyphenations,
+            HashMap::from([("hyphenation".to_string(), "hy=phen=ation".to_string())])
+        );
+
+        let (words, lang) = parse(FIXTURE).unwrap();
+        let mut sorted: Vec<&String> = words.iter().collect();
+        sorted.sort();
+        let content = render(&sorted, lang.as_deref().unwrap(), &hyphenations);
+        assert!(content.contains("\nhy=phen=ation\n"), "{}", content);
+
+        let path = Path::new("standard.dic");
+        let new = String::from("new");
+        let hyphenated = String::from("hyphenation");
+        let bytes = LibreOffice
+            .serialize(
+                path,
+                &[&new, &hyphenated],
+                &Metadata::default(),
+                FIXTURE.as_bytes(),
+            )
+            .unwrap();
+        assert!(String::from_utf8(bytes)
+            .unwrap()
+            .ends_with("---\nnew\nhy=phen=ation\n"));
+    }
+}
@@ -0,0 +1,269 @@
+//! On disk layouts of dictionary files.
+//!
+//! Each layout implements `DictionaryFormat`; `FormatOptions::dictionary_format()` picks the implementation for a
+//! dictionary entry. Adding a format means adding a module here and a `Format` variant; nothing that reads, merges
+//! or writes dictionaries needs to change.
+
+pub mod aspell;
+pub mod chromium;
+pub mod hunspell;
+pub mod jetbrains;
+pub mod json;
+pub mod libreoffice;
+pub mod msword;
+pub mod vim;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::user_dictionary::ChecksumStatus;
+use crate::validation::{EncodingErrorPolicy, InvalidWordPolicy};
+
+/// Layout of a dictionary file, set with `format = "..."` on a dictionary entry
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// One word per line followed by a `checksum_v1 = ` line; used by Obsidian and every other Electron app
+    #[default]
+    Chromium,
+    /// Hunspell `.dic`: word count on the first line then one word per line
+    Hunspell,
+    /// Aspell personal word list: `personal_ws-1.1 <lang> <count>` header then one word per line
+    Aspell,
+    /// Vim spell file (`.add`): one word per line, words removed with `zug` are commented out
+    Vim,
+    /// Array of words somewhere in a JSON or JSONC file, e.g. `cSpell.words` in VS Code's `settings.json`
+    Json,
+    /// JetBrains IDE project dictionary: `<w>` elements in a `ProjectDictionaryState` XML file
+    JetBrains,
+    /// Microsoft Word `CUSTOM.DIC`: UTF-16 little endian with a BOM, one word per line
+    MsWord,
+    /// LibreOffice user dictionary (`standard.dic`): `OOoUserDict1` header then one word per line
+    LibreOffice,
+}
+
+impl Format {
+    /// True if files in this format end with a checksum line that has to match the words
+    pub fn has_checksum(&self) -> bool {
+        matches!(self, Format::Chromium)
+    }
+
+    /// True if files in this format record the language of their words
+    pub fn has_lang(&self) -> bool {
+        matches!(self, Format::Aspell | Format::LibreOffice)
+    }
+
+    /// True if the words are nested somewhere in the file and a `key` is needed to find them
+    pub fn has_key(&self) -> bool {
+        matches!(self, Format::Json)
+    }
+
+    /// True if every line of a file in this format stands on its own, so one that can't be decoded can be left out
+    pub fn has_word_per_line(&self) -> bool {
+        matches!(
+            self,
+            Format::Chromium
+                | Format::Hunspell
+                | Format::Aspell
+                | Format::Vim
+                | Format::LibreOffice
+        )
+    }
+
+    /// True if files in this format are UTF-8 text that may use either line ending and may start with a BOM
+    pub fn has_text_layout(&self) -> bool {
+        !matches!(self, Format::MsWord)
+    }
+}
+
+/// Line ending of a text file, set with `line_ending = "..."` on a dictionary entry
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// `\n`, what every app writes on Linux and macOS
+    #[default]
+    Lf,
+    /// `\r\n`, what files touched by Windows tools often end up with
+    Crlf,
+}
+
+/// How a text file is laid out beyond its words; kept as it was found when the file is rewritten
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextLayout {
+    pub line_ending: LineEnding,
+    /// File starts with a UTF-8 byte order mark
+    pub bom: bool,
+}
+
+impl TextLayout {
+    /// Encodes text that uses `\n` line endings in this layout
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        let mut encoded = String::with_capacity(text.len() + UTF8_BOM.len());
+        if self.bom {
+            encoded.push_str(UTF8_BOM);
+        }
+        match self.line_ending {
+            LineEnding::Lf => encoded.push_str(text),
+            LineEnding::Crlf => encoded.push_str(&text.replace('\n', "\r\n")),
+        }
+        encoded.into_bytes()
+    }
+}
+
+/// The settings on a dictionary entry that decide how its file is read and written
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormatOptions {
+    pub format: Format,
+    /// Language tag for formats that record one; overrides whatever the file has
+    pub lang: Option<String>,
+    /// Dotted path to the words for formats that nest them
+    pub key: Option<String>,
+    /// Line ending to write; overrides whatever the file has
+    pub line_ending: Option<LineEnding>,
+    /// Whether to write a UTF-8 BOM; overrides whatever the file has
+    pub bom: Option<bool>,
+    /// What to do with entries in the file that are not valid words
+    pub invalid_words: InvalidWordPolicy,
+    /// What to do with lines in the file that are not valid UTF-8
+    pub encoding_errors: EncodingErrorPolicy,
+}
+
+impl FormatOptions {
+    /// The implementation that reads and writes files in this format
+    pub fn dictionary_format(&self) -> Box<dyn DictionaryFormat> {
+        match self.format {
+            Format::Chromium => Box::new(chromium::Chromium),
+            Format::Hunspell => Box::new(hunspell::Hunspell),
+            Format::Aspell => Box::new(aspell::Aspell),
+            Format::Vim => Box::new(vim::Vim),
+            Format::Json => Box::new(json::Json {
+                key: self.key.clone().unwrap_or_default(),
+            }),
+            Format::JetBrains => Box::new(jetbrains::JetBrains),
+            Format::MsWord => Box::new(msword::MsWord),
+            Format::LibreOffice => Box::new(libreoffice::LibreOffice),
+        }
+    }
+}
+
+/// Everything a format can record about the words besides the words themselves
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    /// State of the checksum line; `NotApplicable` for formats without one
+    pub checksum: ChecksumStatus,
+    /// Digest on the checksum line, if the file has exactly one
+    pub stored_checksum: Option<String>,
+    /// Language tag, for formats that record one
+    pub lang: Option<String>,
+    /// Words the file marks as removed rather than leaving out, e.g. commented out in a Vim spell file
+    pub deleted: HashSet<String>,
+    /// Line ending and BOM of a text file
+    pub layout: TextLayout,
+}
+
+/// The words in a dictionary file and what else the format had to say about them
+#[derive(Debug, Default, PartialEq)]
+pub struct Parsed {
+    pub words: HashSet<String>,
+    pub metadata: Metadata,
+}
+
+/// Why a file could not be read or written in a format
+#[derive(Debug, PartialEq)]
+pub enum FormatError {
+    /// Bytes are not text in the encoding the format uses
+    Encoding(String),
+    /// Text that is not laid out the way the format expects
+    Invalid(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::Encoding(message) | FormatError::Invalid(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl From<FormatError> for io::Error {
+    fn from(err: FormatError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+    }
+}
+
+/// Reads and writes one on disk layout
+pub trait DictionaryFormat {
+    /// Reads the words and metadata from the raw bytes of the file at `path`
+    fn parse(&self, path: &Path, bytes: &[u8]) -> Result<Parsed, FormatError>;
+
+    /// Renders `words` (sorted) as the bytes of a file. `existing` is what the file holds now (empty for a new file);
+    /// formats that only own part of a file keep the rest of it.
+    fn serialize(
+        &self,
+        path: &Path,
+        words: &[&String],
+        metadata: &Metadata,
+        existing: &[u8],
+    ) -> Result<Vec<u8>, FormatError>;
+
+    /// Called once the file at `path` has been written, e.g. to create files that have to go with it
+    fn after_write(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+const UTF8_BOM: &str = "\u{FEFF}";
+
+/// Decodes the bytes of a UTF-8 text file into text with `\n` line endings and no BOM, along with the layout the
+/// file had. A file is CRLF if most of its lines are.
+pub(crate) fn text(bytes: &[u8]) -> Result<(String, TextLayout), FormatError> {
+    let content =
+        std::str::from_utf8(bytes).map_err(|err| FormatError::Encoding(err.to_string()))?;
+    let (content, bom) = match content.strip_prefix(UTF8_BOM) {
+        Some(rest) => (rest, true),
+        None => (content, false),
+    };
+    let crlf = content.matches("\r\n").count();
+    let line_ending = if crlf > 0 && crlf * 2 >= content.matches('\n').count() {
+        LineEnding::Crlf
+    } else {
+        LineEnding::Lf
+    };
+    let mut normalized = content.replace("\r\n", "\n");
+    // A last line without a line ending still shouldn't keep half of one
+    if normalized.ends_with('\r') {
+        normalized.pop();
+    }
+    Ok((normalized, TextLayout { line_ending, bom }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_layout_round_trip() {
+        let (content, layout) = text(b"\xEF\xBB\xBFalpha\r\nbeta\r\n").unwrap();
+        assert_eq!(content, "alpha\nbeta\n");
+        assert_eq!(
+            layout,
+            TextLayout {
+                line_ending: LineEnding::Crlf,
+                bom: true
+            }
+        );
+        assert_eq!(layout.encode(&content), b"\xEF\xBB\xBFalpha\r\nbeta\r\n");
+
+        let (content, layout) = text(b"alpha\nbeta\r").unwrap();
+        assert_eq!(content, "alpha\nbeta");
+        assert_eq!(layout, TextLayout::default());
+
+        assert!(matches!(text(b"caf\xe9"), Err(FormatError::Encoding(_))));
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::{DictionaryFormat, FormatError, Metadata, Parsed};
+
+/// Byte order mark Word writes at the start of `CUSTOM.DIC`
+const BOM: [u8; 2] = [0xFF, 0xFE];
+
+/// Decodes a Microsoft Word custom dictionary (`CUSTOM.DIC`): UTF-16 little endian with a BOM.
+/// Files without the BOM are taken to be UTF-8, which is what older versions of Word and other tools write.
+pub fn decode(bytes: &[u8]) -> Result<String, String> {
+    let Some(utf16) = bytes.strip_prefix(&BOM) else {
+        return String::from_utf8(bytes.to_vec()).map_err(|err| err.to_string());
+    };
+    if utf16.len() % 2 != 0 {
+        return Err(String::from("odd number of bytes for UTF-16"));
+    }
+    let units: Vec<u16> = utf16
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).map_err(|err| err.to_string())
+}
+
+/// Every non-empty line of the decoded file is a word
+pub fn parse(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Renders words the way Word writes them: BOM, UTF-16 little endian and Windows line endings
+pub fn render(words: &[&String]) -> Vec<u8> {
+    let mut bytes = BOM.to_vec();
+    for word in words {
+        for unit in word.encode_utf16().chain("\r\n".encode_utf16()) {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// Microsoft Word custom dictionaries; the only format that isn't UTF-8
+#[derive(Debug)]
+pub struct MsWord;
+
+impl DictionaryFormat for MsWord {
+    fn parse(&self, _path: &Path, bytes: &[u8]) -> Result<Parsed, FormatError> {
+        let content = decode(bytes).map_err(FormatError::Encoding)?;
+        Ok(Parsed {
+            words: parse(&content),
+            ..Parsed::default()
+        })
+    }
+
+    fn serialize(
+        &self,
+        _path: &Path,
+        words: &[&String],
+        _metadata: &Metadata,
+        _existing: &[u8],
+    ) -> Result<Vec<u8>, FormatError> {
+        Ok(render(words))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::words;
+
+    /// Laid out the way Word writes the file rather than the way `render()` does: BOM, UTF-16 little endian, CRLF
+    /// after every word, words in the order they were added to the dictionary instead of sorted
+    const FIXTURE: &[u8] = include_bytes!("fixtures/CUSTOM.DIC");
+
+    #[test]
+    fn test_parse_fixture() {
+        assert!(FIXTURE.starts_with(&BOM));
+        assert!(FIXTURE.ends_with(&[0x0D, 0x00, 0x0A, 0x00]));
+        let content = decode(FIXTURE).unwrap();
+        assert_eq!(
+            parse(&content),
+            words(&["Obsidian", "naïve", "Zettelkasten", "café"])
+        );
+
+        // No BOM; plain UTF-8
+        assert_eq!(parse(&decode(b"alpha\r\n").unwrap()), words(&["alpha"]));
+        assert!(decode(&[0xFF, 0xFE, 0x41]).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let a = String::from("a");
+        let e = String::from("é");
+        assert_eq!(
+            render(&[&a, &e]),
+            [0xFF, 0xFE, 0x61, 0x00, 0x0D, 0x00, 0x0A, 0x00, 0xE9, 0x00, 0x0D, 0x00, 0x0A, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_fixture_round_trip() {
+        let words = parse(&decode(FIXTURE).unwrap());
+        let mut sorted: Vec<&String> = words.iter().collect();
+        sorted.sort();
+        let rendered = render(&sorted);
+        // Same words and layout; only the order differs
+        assert_eq!(rendered.len(), FIXTURE.len());
+        assert_ne!(rendered, FIXTURE);
+        assert_eq!(parse(&decode(&rendered).unwrap()), words);
+    }
+}
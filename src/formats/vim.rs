@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::{text, DictionaryFormat, FormatError, Metadata, Parsed};
+
+/// Reads a Vim spell file (`:help spell-wordlist-format`), e.g. `~/.vim/spell/en.utf-8.add`.
+///
+/// Returns the words and the words that are commented out. `zug` comments a word out rather than deleting the line
+/// so those are words the user removed, unless the word was added back further down the file. Words marked bad with
+/// `zw` (`word/!`) are not words to sync and are skipped.
+pub fn parse(content: &str) -> (HashSet<String>, HashSet<String>) {
+    let mut words = HashSet::new();
+    let mut commented = HashSet::new();
+    for line in content.lines() {
+        let line = line.trim_end();
+        let (entry, is_comment) = match line.strip_prefix('#') {
+            Some(entry) => (entry.trim_start(), true),
+            None => (line, false),
+        };
+        // Flags follow the last slash; a word containing a slash has an empty set of flags after it
+        let (word, flags) = entry.rsplit_once('/').unwrap_or((entry, ""));
+        if word.is_empty() || flags.contains('!') {
+            continue;
+        }
+        if is_comment {
+            commented.insert(word.to_string());
+        } else {
+            words.insert(word.to_string());
+        }
+    }
+    let deleted = commented.difference(&words).cloned().collect();
+    (words, deleted)
+}
+
+/// Renders words as a Vim spell file; commented out words are gone once the removal has been synced
+pub fn render(words: &[&String]) -> String {
+    let mut content = String::new();
+    for word in words {
+        content.push_str(word);
+        if word.contains('/') {
+            content.push('/');
+        }
+        content.push('\n');
+    }
+    content
+}
+
+/// Vim spell files
+#[derive(Debug)]
+pub struct Vim;
+
+impl DictionaryFormat for Vim {
+    fn parse(&self, _path: &Path, bytes: &[u8]) -> Result<Parsed, FormatError> {
+        let (content, layout) = text(bytes)?;
+        let (words, deleted) = parse(&content);
+        Ok(Parsed {
+            words,
+            metadata: Metadata {
+                deleted,
+                layout,
+                ..Metadata::default()
+            },
+        })
+    }
+
+    fn serialize(
+        &self,
+        _path: &Path,
+        words: &[&String],
+        metadata: &Metadata,
+        _existing: &[u8],
+    ) -> Result<Vec<u8>, FormatError> {
+        Ok(metadata.layout.encode(&render(words)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::words;
+
+    #[test]
+    fn test_parse() {
+        let (parsed, deleted) =
+            parse("Obsidian\n#FitBit\nteh/!\nVim/=\nand/or/\n#again\nagain\n\n");
+        assert_eq!(parsed, words(&["Obsidian", "Vim", "and/or", "again"]));
+        assert_eq!(deleted, words(&["FitBit"]));
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let a = String::from("and/or");
+        let b = String::from("Obsidian");
+        let content = render(&[&a, &b]);
+        assert_eq!(content, "and/or/\nObsidian\n");
+        assert_eq!(
+            parse(&content),
+            (words(&["and/or", "Obsidian"]), HashSet::new())
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! Keeps Obsidian.md (Chromium style) user dictionaries on several machines in sync.
+//!
+//! The `obs-dict-sync` binary is a thin wrapper around this crate; anything it can do can also be done by
+//! loading a [`Config`] and calling [`sync()`] directly:
+//!
+//! ```
+//! use obs_dict_sync::{sync, Config};
+//!
+//! # let dir = tempfile::tempdir()?;
+//! # std::env::set_current_dir(dir.path())?;
+//! # // Never the real Obsidian dictionary of whoever runs the tests
+//! # std::env::set_var("HOME", dir.path());
+//! let config = Config::parse(
+//!     r#"
+//!     [dictionaries.authoritative]
+//!     path = "./master_dictionary.txt"
+//!     [dictionaries.laptop]
+//!     path = "~/.config/obsidian/Custom Dictionary.txt"
+//!     [filters]
+//!     remove = []
+//!     "#,
+//! )?;
+//! let report = sync(&config)?;
+//! println!("All dictionaries now have {} words", report.words.len());
+//! # Ok::<(), obs_dict_sync::Error>(())
+//! ```
+
+#[macro_use]
+extern crate log;
+
+pub mod backup;
+pub mod config;
+pub mod diff;
+pub mod discovery;
+pub mod edit;
+pub mod error;
+pub mod filter;
+pub mod formats;
+pub mod merge;
+pub mod presets;
+pub mod query;
+pub mod status;
+pub mod sync;
+#[cfg(test)]
+mod test_util;
+pub mod user_dictionary;
+pub mod validation;
+
+pub use crate::config::Config;
+pub use crate::diff::DictionaryDiff;
+pub use crate::edit::{edit, Edit};
+pub use crate::error::Error;
+pub use crate::sync::{dry_run, sync, verify, SyncOutcome, SyncReport, VerifyOutcome};
+pub use crate::user_dictionary::{calculate_hash_digest, ChecksumStatus, UserDictionary};
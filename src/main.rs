@@ -1,22 +1,37 @@
 mod build_info;
 mod cli;
-mod config;
-mod user_dictionary;
 
 #[macro_use]
 extern crate log;
 
-use crate::config::Config;
-use crate::user_dictionary::UserDictionary;
-use clap::Parser;
+use crate::cli::{Command, OutputFormat};
+use clap::{CommandFactory, Parser};
 use env_logger::{Builder, Target};
 use log::{debug, info};
-use std::collections::HashSet;
+use obs_dict_sync::discovery::{self, DiscoveryEnv};
+use obs_dict_sync::query::{self, SearchMode};
+use obs_dict_sync::status::DictionaryStatus;
+use obs_dict_sync::user_dictionary::get_hydrated_path_from_str;
+use obs_dict_sync::{backup, Config, Edit, Error, SyncOutcome};
+use regex::RegexBuilder;
+use std::io;
+use std::process::exit;
 
 fn main() {
     let args = cli::Args::parse();
-    setup_logging(args.verbose);
+    setup_logging(
+        args.verbose,
+        args.command.as_ref().is_some_and(Command::is_query),
+    );
+
+    // Each kind of failure has its own exit code so whatever spawned us can tell them apart; see error.rs
+    if let Err(err) = run(args) {
+        error!("{}", err);
+        exit(err.exit_code());
+    }
+}
 
+fn run(args: cli::Args) -> Result<(), Error> {
     info!("Obsidian Dictionary Sync v{}.", env!("CARGO_PKG_VERSION"));
 
     debug!(
@@ -27,101 +42,310 @@ fn main() {
     );
 
     // Get the current working directory and config file path for relative path fixing in a moment...
-    let cfg_file_path = args.config_file_path.unwrap();
-    info!(
-        "Loading config file from: {}",
-        cfg_file_path.to_str().unwrap()
-    );
+    let cfg_file_path = &args.config_file_path;
+    info!("Loading config file from: {}", cfg_file_path.display());
 
-    let cwd = std::env::current_dir().unwrap();
-    debug!("cwd: {}", cwd.to_str().unwrap());
+    let cwd = std::env::current_dir()?;
+    debug!("cwd: {}", cwd.display());
 
     // Render/Parse config file
-    let config = Config::new_from_file(&cfg_file_path);
+    let config = Config::new_from_file(cfg_file_path)?;
     debug!("Parsed config: {:#?}", &config);
 
-    // Load up the authoritative dictionary
-    if !config.dictionaries.contains_key("authoritative") {
-        panic!("The config file must have a dictionary named `authoritative` present!");
+    match &args.command {
+        Some(Command::Restore { run, dictionary }) => {
+            let backup_dir = get_hydrated_path_from_str(&config.backup.dir)?;
+            return run_restore(
+                &backup_dir,
+                config.backup.keep,
+                run.as_deref(),
+                dictionary.as_deref(),
+                args.dry_run,
+            );
+        }
+        // Only ever reads so there is nothing for a dry run to hold back
+        Some(Command::Verify) => return run_verify(&config),
+        Some(Command::Add { words, all }) => {
+            return run_edit(&config, Edit::Add(words.clone()), *all, args.dry_run);
+        }
+        Some(Command::Remove { words, all }) => {
+            return run_edit(&config, Edit::Remove(words.clone()), *all, args.dry_run);
+        }
+        Some(Command::Discover) => return run_discover(&config),
+        Some(Command::Status { json }) => return run_status(&config, *json),
+        Some(Command::List { query }) => return run_query(&config, query, None),
+        Some(Command::Search {
+            pattern,
+            regex,
+            fuzzy,
+            max_distance,
+            ignore_case,
+            query,
+        }) => {
+            let mode = if *regex {
+                match RegexBuilder::new(pattern)
+                    .case_insensitive(*ignore_case)
+                    .build()
+                {
+                    Ok(regex) => SearchMode::Regex(regex),
+                    // Bad pattern is a bad argument; let clap report it like any other
+                    Err(err) => cli::Args::command()
+                        .error(clap::error::ErrorKind::ValueValidation, err)
+                        .exit(),
+                }
+            } else if *fuzzy {
+                SearchMode::Fuzzy {
+                    text: pattern.clone(),
+                    max_distance: *max_distance,
+                }
+            } else {
+                SearchMode::Substring {
+                    text: pattern.clone(),
+                    ignore_case: *ignore_case,
+                }
+            };
+            return run_query(&config, query, Some(mode));
+        }
+        None => {}
     }
 
-    // Create the authoritative dictionary
-    let mut authoritative_dict =
-        UserDictionary::new_from_pathbuf(config.get_authoritative_dictionary_path())
-            .unwrap_or_else(|err| {
-                panic!("Could not open authoritative dictionary: {}", err);
-            });
+    if args.dry_run {
+        let report = obs_dict_sync::dry_run(&config)?;
+        print_outcomes(&report.dictionaries);
+        info!(
+            "Dry run complete; dictionaries would have been written with '{}' words.",
+            report.words.len()
+        );
+        return Ok(());
+    }
 
-    debug!("authoritative_dict: {:#?}", authoritative_dict);
+    let report = obs_dict_sync::sync(&config)?;
+    debug!("sync report: {:#?}", report);
     info!(
-        "Authoritative Dictionary has {} words",
-        authoritative_dict
-            .words
-            .as_ref()
-            .expect("Failure to get auth-dict words!")
-            .len()
+        "Done! All dictionaries have been written to disk with '{}' words.",
+        report.words.len()
     );
-    // Keep track of which dictionaries we found on disk; we'll have to write combined authoritative list to these
-    let mut user_dictionaries: Vec<UserDictionary> = Vec::new();
-    for (name, data) in config.dictionaries.iter() {
-        info!("Processing dictionary: {}", name);
-        // Toml gives us a string
-        let dict_path = data
-            .as_table()
-            .unwrap()
-            .get("path")
-            .unwrap()
-            .as_str()
-            .unwrap();
-
-        debug!("dictionary '{}' is located at '{}'...", name, dict_path);
-        let user_dictionary = UserDictionary::new_from_file_path(dict_path);
-        match user_dictionary {
-            Err(e) => {
-                warn!("Could not parse dictionary from '{}': {}", dict_path, e);
-                continue;
-            }
-            Ok(ud) => {
-                debug!("user_dictionary: {:#?}", ud);
-                authoritative_dict
-                    // TODO: Is there a way to do this w/o clone()?
-                    .add_words(ud.words.clone().unwrap());
-                user_dictionaries.push(ud);
+    Ok(())
+}
+
+/// Checks the checksum line of every configured dictionary and exits non-zero if any of them are inconsistent.
+/// Dictionaries that don't exist are reported but are not an error; sync skips them too.
+fn run_verify(config: &Config) -> Result<(), Error> {
+    let results = obs_dict_sync::verify(config);
+    for (name, outcome) in results.iter() {
+        println!("[{}] {}", name, outcome);
+    }
+
+    let inconsistent = results.iter().filter(|(_, o)| o.is_inconsistent()).count();
+    if inconsistent > 0 {
+        return Err(Error::ChecksumMismatch(inconsistent));
+    }
+    info!("All dictionaries are consistent.");
+    Ok(())
+}
+
+/// Adds or removes words and prints what changed in each dictionary
+fn run_edit(config: &Config, edit: Edit, fan_out: bool, dry_run: bool) -> Result<(), Error> {
+    let report = obs_dict_sync::edit(config, &edit, fan_out, dry_run)?;
+    print_outcomes(&report);
+    if !fan_out {
+        info!("Run a sync (or pass --all) to carry the change to the other dictionaries.");
+    }
+    Ok(())
+}
+
+/// Prints ready to paste `[dictionaries.*]` entries for every dictionary found in the well known locations
+fn run_discover(config: &Config) -> Result<(), Error> {
+    let env = DiscoveryEnv::from_env().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "$HOME is not set; nowhere to look for dictionaries",
+        )
+    })?;
+    let found = discovery::discover(&env, config);
+    println!("# Found {} dictionaries", found.len());
+    print!("{}", discovery::to_config_entries(&env, &found));
+    Ok(())
+}
+
+/// Prints how far each dictionary has drifted from the authoritative dictionary
+fn run_status(config: &Config, json: bool) -> Result<(), Error> {
+    let statuses = obs_dict_sync::status::status(config)?;
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&statuses).map_err(io::Error::from)?
+        );
+    } else {
+        print_status_table(&statuses);
+    }
+
+    let out_of_sync = statuses.iter().filter(|s| !s.in_sync).count();
+    if out_of_sync > 0 {
+        return Err(Error::OutOfSync(out_of_sync));
+    }
+    Ok(())
+}
+
+fn print_status_table(statuses: &[DictionaryStatus]) {
+    let header = [
+        "DICTIONARY",
+        "EXISTS",
+        "WORDS",
+        "EXTRA",
+        "MISSING",
+        "CHECKSUM",
+        "INVALID",
+        "IN SYNC",
+    ];
+    let yes_no = |b: bool| String::from(if b { "yes" } else { "no" });
+    let rows: Vec<[String; 8]> = statuses
+        .iter()
+        .map(|s| {
+            let name = match (s.enabled, s.readonly) {
+                (false, _) => format!("{} (disabled)", s.name),
+                (true, true) => format!("{} (readonly)", s.name),
+                (true, false) => s.name.clone(),
+            };
+            let checksum = match (&s.checksum, &s.error) {
+                (_, Some(_)) => String::from("unreadable"),
+                (Some(checksum), _) => checksum.clone(),
+                (None, None) => String::from("-"),
+            };
+            [
+                name,
+                yes_no(s.exists),
+                s.words.to_string(),
+                s.extra.to_string(),
+                s.missing.to_string(),
+                checksum,
+                s.invalid.to_string(),
+                yes_no(s.in_sync),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.map(str::len);
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let print_row = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(header.to_vec());
+    for row in rows.iter() {
+        print_row(row.iter().map(String::as_str).collect());
+    }
+    for s in statuses.iter() {
+        if let Some(error) = &s.error {
+            println!("[{}] {}", s.name, error);
+        }
+    }
+}
+
+/// Lists (and optionally searches) the words in every dictionary
+fn run_query(
+    config: &Config,
+    query: &cli::QueryArgs,
+    mode: Option<SearchMode>,
+) -> Result<(), Error> {
+    let mut entries = query::list(config, &query.in_dictionaries, &query.not_in_dictionaries)?;
+    if let Some(mode) = mode {
+        entries = query::search(entries, &mode);
+    }
+
+    match query.format {
+        OutputFormat::Plain => {
+            for entry in entries.iter() {
+                println!("{}", entry.word);
             }
         }
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&entries).map_err(io::Error::from)?
+            );
+        }
+        OutputFormat::Count => println!("{}", entries.len()),
     }
-    debug!("Found '{}' user dictionaries...", user_dictionaries.len());
-    // After loading in all words from all dictionaries, remove filtered words from the authoritative dictionary
-    debug!("config.filters.remove: {:#?}", config.filters.remove);
+    Ok(())
+}
 
-    // TODO: figure out how to do the conversion on config parse so filters.remove is already
-    // proper type / doesn't need conversion?
-    authoritative_dict.remove_words(HashSet::from_iter(config.filters.remove));
+/// Lists the available backup runs or restores one of them; on a dry run, only lists what would be restored
+fn run_restore(
+    backup_dir: &std::path::Path,
+    keep: usize,
+    run: Option<&str>,
+    dictionary: Option<&str>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let run = match run {
+        Some(run) => run,
+        None => {
+            let runs = backup::list_runs(backup_dir)?;
+            if runs.is_empty() {
+                println!("No backups found in '{}'", backup_dir.display());
+            }
+            for manifest in runs {
+                let names: Vec<&str> = manifest.files.iter().map(|f| f.name.as_str()).collect();
+                println!(
+                    "{}  ({} files: {})",
+                    manifest.run,
+                    names.len(),
+                    names.join(", ")
+                );
+            }
+            return Ok(());
+        }
+    };
 
-    // Write the authoritative dictionary to disk
-    debug!("authoritative_dict => '{}' ", &authoritative_dict);
-    authoritative_dict.write_to_disk().unwrap();
+    if dry_run {
+        for file in backup::files_to_restore(backup_dir, run, dictionary)? {
+            println!(
+                "[{}] '{}' would be restored from '{}'",
+                file.name,
+                file.path.display(),
+                run
+            );
+        }
+        return Ok(());
+    }
 
-    // Iterate through the dictionary file(s) we did find on disk and write the authoritative dictionary to them
-    for mut user_dict in user_dictionaries {
-        // TODO: Is there a way to do this w/o clone()? At this point in code flow, the authoritative dictionary
-        // is fixed and will not change.
-        user_dict.set_words(authoritative_dict.words.clone().unwrap());
-        user_dict.write_to_disk().unwrap();
+    let restored = backup::restore(backup_dir, run, dictionary, keep)?;
+    info!("Restored '{}' dictionaries from '{}'", restored.len(), run);
+    Ok(())
+}
+
+/// Prints what happened (or would happen) to each configured dictionary
+fn print_outcomes(outcomes: &[(String, SyncOutcome)]) {
+    for (name, outcome) in outcomes.iter() {
+        println!("[{}] {}", name, outcome);
     }
-    info!(
-        "Done! All dictionaries have been written to disk with '{}' words.",
-        authoritative_dict.words.as_ref().unwrap().len()
-    );
 }
 
-fn setup_logging(user_verbose: bool) {
+/// Logs go to stdout so they show up in Obsidian when run from Templater.
+/// Query commands print results to stdout that may be piped elsewhere so their logs go to stderr and only
+/// warnings are shown unless verbose.
+fn setup_logging(user_verbose: bool, query: bool) {
     let mut builder = Builder::from_default_env();
     if user_verbose {
         builder.filter_level(log::LevelFilter::Debug);
+    } else if query {
+        builder.filter_level(log::LevelFilter::Warn);
     } else {
         builder.filter_level(log::LevelFilter::Info);
     }
-    builder.target(Target::Stdout);
+    if query {
+        builder.target(Target::Stderr);
+    } else {
+        builder.target(Target::Stdout);
+    }
     builder.init();
 }
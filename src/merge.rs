@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::formats::FormatOptions;
+use crate::user_dictionary::UserDictionary;
+use crate::validation::InvalidWordPolicy;
+
+/// Suffix appended to the authoritative dictionary file name to get the base snapshot file name
+const BASE_SNAPSHOT_SUFFIX: &str = ".base";
+
+/// Suffix appended to the base snapshot file name to get the file listing the dictionaries synced so far
+const SYNCED_DICTIONARIES_SUFFIX: &str = ".dictionaries";
+
+/// The base snapshot lives right next to the authoritative dictionary.
+/// E.g. `./master_dictionary.txt` => `./master_dictionary.txt.base`
+pub fn base_snapshot_path(authoritative_path: &Path) -> PathBuf {
+    let mut file_name = authoritative_path
+        .file_name()
+        .unwrap_or_default()
+        .to_os_string();
+    file_name.push(BASE_SNAPSHOT_SUFFIX);
+    authoritative_path.with_file_name(file_name)
+}
+
+/// Reads the base snapshot exactly as it was written. It holds every word the dictionaries were synced with,
+/// including entries kept by `invalid_words = "keep"`, so none of them are trimmed or dropped.
+pub fn read_base_snapshot(path: PathBuf) -> Result<UserDictionary, Error> {
+    let options = FormatOptions {
+        invalid_words: InvalidWordPolicy::Keep,
+        ..FormatOptions::default()
+    };
+    UserDictionary::new_from_pathbuf_with_options(path, &options)
+}
+
+/// The list of dictionaries synced so far lives right next to the base snapshot.
+/// E.g. `./master_dictionary.txt` => `./master_dictionary.txt.base.dictionaries`
+pub fn synced_dictionaries_path(authoritative_path: &Path) -> PathBuf {
+    let base_path = base_snapshot_path(authoritative_path);
+    let mut file_name = base_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(SYNCED_DICTIONARIES_SUFFIX);
+    base_path.with_file_name(file_name)
+}
+
+/// Reads the paths of the dictionaries that have been written by a sync, one per line.
+/// A missing file means no dictionary has been synced yet.
+pub fn read_synced_dictionaries(path: &Path) -> io::Result<HashSet<PathBuf>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes the paths of the dictionaries that have been written by a sync, sorted, one per line
+pub fn write_synced_dictionaries(path: &Path, dictionaries: &HashSet<PathBuf>) -> io::Result<()> {
+    let mut lines: Vec<String> = dictionaries
+        .iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    lines.sort();
+    let mut content = lines.join("\n");
+    content.push('\n');
+    fs::write(path, content)
+}
+
+/// Merges the words from every dictionary against the word set recorded at the end of the last sync.
+///
+/// - A word that is in any dictionary but not in the base was added somewhere; it is kept.
+/// - A word that is in the base but missing from at least one dictionary was removed somewhere; it is dropped
+///   from the result so the removal propagates to every dictionary.
+///
+/// Only `dictionaries` that were written with the base words can be expected to contain them. Readonly dictionaries
+/// are never rewritten and a dictionary new to the sync (e.g. just added to the config) has never been written, so
+/// both go in `additions_only`; their words are only ever additions.
+///
+/// Without a base (e.g. first run) there is nothing to compare against so this is just the union of all words.
+pub fn three_way_merge(
+    base: Option<&HashSet<String>>,
+    dictionaries: &[&HashSet<String>],
+    additions_only: &[&HashSet<String>],
+) -> HashSet<String> {
+    let union: HashSet<String> = dictionaries
+        .iter()
+        .chain(additions_only.iter())
+        .flat_map(|words| words.iter())
+        .cloned()
+        .collect();
+
+    let base = match base {
+        None => {
+            debug!("No base snapshot, merged result is the union of all dictionaries.");
+            return union;
+        }
+        Some(base) => base,
+    };
+
+    let deleted: HashSet<&String> = base
+        .iter()
+        .filter(|word| dictionaries.iter().any(|words| !words.contains(*word)))
+        .collect();
+    debug!(
+        "'{}' words were removed from at least one dictionary since the last sync",
+        deleted.len()
+    );
+
+    union
+        .into_iter()
+        .filter(|word| !deleted.contains(word))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::words;
+
+    #[test]
+    fn test_base_snapshot_path() {
+        assert_eq!(
+            base_snapshot_path(Path::new("/tmp/master_dictionary.txt")),
+            PathBuf::from("/tmp/master_dictionary.txt.base")
+        );
+    }
+
+    #[test]
+    fn test_synced_dictionaries() -> io::Result<()> {
+        assert_eq!(
+            synced_dictionaries_path(Path::new("/tmp/master_dictionary.txt")),
+            PathBuf::from("/tmp/master_dictionary.txt.base.dictionaries")
+        );
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("master.txt.base.dictionaries");
+        assert!(read_synced_dictionaries(&path)?.is_empty());
+
+        let synced = HashSet::from([PathBuf::from("/b.txt"), PathBuf::from("/a dir/a.txt")]);
+        write_synced_dictionaries(&path, &synced)?;
+        assert_eq!(fs::read_to_string(&path)?, "/a dir/a.txt\n/b.txt\n");
+        assert_eq!(read_synced_dictionaries(&path)?, synced);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_without_base_is_union() {
+        let a = words(&["alpha", "beta"]);
+        let b = words(&["beta", "gamma"]);
+        let merged = three_way_merge(None, &[&a, &b], &[]);
+        assert_eq!(merged, words(&["alpha", "beta", "gamma"]));
+    }
+
+    #[test]
+    fn test_merge_propagates_deletions() {
+        let base = words(&["alpha", "beta", "typo"]);
+        // `typo` was removed on one machine, `gamma` was added on another
+        let a = words(&["alpha", "beta"]);
+        let b = words(&["alpha", "beta", "typo", "gamma"]);
+        let merged = three_way_merge(Some(&base), &[&a, &b], &[]);
+        assert_eq!(merged, words(&["alpha", "beta", "gamma"]));
+    }
+
+    #[test]
+    fn test_merge_keeps_words_added_after_base() {
+        // Word is missing from one dictionary but was never synced so it's an addition, not a deletion
+        let base = words(&["alpha"]);
+        let a = words(&["alpha", "new"]);
+        let b = words(&["alpha"]);
+        let merged = three_way_merge(Some(&base), &[&a, &b], &[]);
+        assert_eq!(merged, words(&["alpha", "new"]));
+    }
+
+    #[test]
+    fn test_merge_readonly_only_adds() {
+        let base = words(&["alpha", "beta"]);
+        let a = words(&["alpha", "beta"]);
+        // Readonly dictionary is missing `beta` but that is not a deletion
+        let readonly = words(&["alpha", "gamma"]);
+        let merged = three_way_merge(Some(&base), &[&a], &[&readonly]);
+        assert_eq!(merged, words(&["alpha", "beta", "gamma"]));
+
+        // Nor is it for a dictionary that was never synced before
+        let new = words(&[]);
+        let merged = three_way_merge(Some(&base), &[&a], &[&new]);
+        assert_eq!(merged, words(&["alpha", "beta"]));
+    }
+}
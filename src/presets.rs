@@ -0,0 +1,128 @@
+use serde::Deserialize;
+
+use crate::discovery::DICTIONARY_FILE_NAME;
+
+/// Profile used by Chromium based browsers when none is configured
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// Apps that keep their dictionary in the same checksummed format Obsidian does.
+/// A dictionary entry can name one of these with `preset = "..."` instead of spelling out the `path`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    /// Native / .deb / AppImage install
+    Obsidian,
+    ObsidianFlatpak,
+    ObsidianSnap,
+    Vscode,
+    Vscodium,
+    /// Has one dictionary per browser profile
+    Chromium,
+    /// Has one dictionary per browser profile
+    Chrome,
+    Slack,
+    Discord,
+    Signal,
+    Logseq,
+    Joplin,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 12] = [
+        Preset::Obsidian,
+        Preset::ObsidianFlatpak,
+        Preset::ObsidianSnap,
+        Preset::Vscode,
+        Preset::Vscodium,
+        Preset::Chromium,
+        Preset::Chrome,
+        Preset::Slack,
+        Preset::Discord,
+        Preset::Signal,
+        Preset::Logseq,
+        Preset::Joplin,
+    ];
+
+    /// Name used for the preset in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Obsidian => "obsidian",
+            Preset::ObsidianFlatpak => "obsidian_flatpak",
+            Preset::ObsidianSnap => "obsidian_snap",
+            Preset::Vscode => "vscode",
+            Preset::Vscodium => "vscodium",
+            Preset::Chromium => "chromium",
+            Preset::Chrome => "chrome",
+            Preset::Slack => "slack",
+            Preset::Discord => "discord",
+            Preset::Signal => "signal",
+            Preset::Logseq => "logseq",
+            Preset::Joplin => "joplin",
+        }
+    }
+
+    /// Where the app keeps its (Electron / Chromium) user data on Linux, relative to the home directory
+    pub fn user_data_dir(&self) -> &'static str {
+        match self {
+            Preset::Obsidian => ".config/obsidian",
+            Preset::ObsidianFlatpak => ".var/app/md.obsidian.Obsidian/config/obsidian",
+            Preset::ObsidianSnap => "snap/obsidian/current/.config/obsidian",
+            Preset::Vscode => ".config/Code",
+            Preset::Vscodium => ".config/VSCodium",
+            Preset::Chromium => ".config/chromium",
+            Preset::Chrome => ".config/google-chrome",
+            Preset::Slack => ".config/Slack",
+            Preset::Discord => ".config/discord",
+            Preset::Signal => ".config/Signal",
+            Preset::Logseq => ".config/Logseq",
+            Preset::Joplin => ".config/Joplin",
+        }
+    }
+
+    /// Browsers keep a dictionary in each profile directory rather than one for the whole app
+    pub fn has_profiles(&self) -> bool {
+        matches!(self, Preset::Chromium | Preset::Chrome)
+    }
+
+    /// Path to the dictionary file in the form used in the config file, e.g. `~/.config/Slack/Custom Dictionary.txt`.
+    /// `profile` is only used by presets that have profiles.
+    pub fn path(&self, profile: Option<&str>) -> String {
+        if self.has_profiles() {
+            format!(
+                "~/{}/{}/{}",
+                self.user_data_dir(),
+                profile.unwrap_or(DEFAULT_PROFILE),
+                DICTIONARY_FILE_NAME
+            )
+        } else {
+            format!("~/{}/{}", self.user_data_dir(), DICTIONARY_FILE_NAME)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_paths() {
+        assert_eq!(
+            Preset::Slack.path(None),
+            "~/.config/Slack/Custom Dictionary.txt"
+        );
+        assert_eq!(
+            Preset::Chrome.path(None),
+            "~/.config/google-chrome/Default/Custom Dictionary.txt"
+        );
+        assert_eq!(
+            Preset::Chromium.path(Some("Profile 1")),
+            "~/.config/chromium/Profile 1/Custom Dictionary.txt"
+        );
+        // Names round trip through the config file spelling
+        for preset in Preset::ALL {
+            let parsed: Preset =
+                Preset::deserialize(toml::Value::String(preset.name().to_string())).unwrap();
+            assert_eq!(parsed, preset);
+        }
+    }
+}
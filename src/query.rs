@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::sync::{load_dictionary, Loaded};
+
+/// A word and the names of every dictionary it was found in
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WordEntry {
+    pub word: String,
+    pub dictionaries: Vec<String>,
+}
+
+/// How `search()` decides that a word matches
+#[derive(Debug)]
+pub enum SearchMode {
+    /// Word contains the text
+    Substring { text: String, ignore_case: bool },
+    /// Regex matches anywhere in the word
+    Regex(Regex),
+    /// Word is within `max_distance` single character edits of the text, ignoring case
+    Fuzzy { text: String, max_distance: usize },
+}
+
+/// Collects every word from every enabled dictionary that can be read, sorted.
+///
+/// - `in_dictionaries`: only keep words that are in every one of these dictionaries
+/// - `not_in_dictionaries`: drop words that are in any of these dictionaries
+pub fn list(
+    config: &Config,
+    in_dictionaries: &[String],
+    not_in_dictionaries: &[String],
+) -> Result<Vec<WordEntry>, Error> {
+    for name in in_dictionaries.iter().chain(not_in_dictionaries) {
+        if !config.dictionaries.contains_key(name) {
+            return Err(Error::UnknownDictionary(name.clone()));
+        }
+    }
+
+    let mut found: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (name, dictionary) in config.dictionaries.iter() {
+        let ud = match load_dictionary(name, dictionary)? {
+            Loaded::Existing(ud) => ud,
+            _ => continue,
+        };
+        for word in ud.get_sorted_words() {
+            found.entry(word.clone()).or_default().push(name.clone());
+        }
+    }
+
+    Ok(found
+        .into_iter()
+        .filter(|(_, names)| in_dictionaries.iter().all(|n| names.contains(n)))
+        .filter(|(_, names)| !not_in_dictionaries.iter().any(|n| names.contains(n)))
+        .map(|(word, dictionaries)| WordEntry { word, dictionaries })
+        .collect())
+}
+
+/// Keeps the entries whose word matches. Fuzzy matches are ordered closest first; otherwise order is kept.
+pub fn search(entries: Vec<WordEntry>, mode: &SearchMode) -> Vec<WordEntry> {
+    match mode {
+        SearchMode::Substring { text, ignore_case } => {
+            let text = if *ignore_case {
+                text.to_lowercase()
+            } else {
+                text.clone()
+            };
+            entries
+                .into_iter()
+                .filter(|entry| {
+                    if *ignore_case {
+                        entry.word.to_lowercase().contains(&text)
+                    } else {
+                        entry.word.contains(&text)
+                    }
+                })
+                .collect()
+        }
+        SearchMode::Regex(regex) => entries
+            .into_iter()
+            .filter(|entry| regex.is_match(&entry.word))
+            .collect(),
+        SearchMode::Fuzzy { text, max_distance } => {
+            let text = text.to_lowercase();
+            let mut scored: Vec<(usize, WordEntry)> = entries
+                .into_iter()
+                .map(|entry| (levenshtein(&entry.word.to_lowercase(), &text), entry))
+                .filter(|(distance, _)| distance <= max_distance)
+                .collect();
+            // Stable sort so equally close words stay alphabetical
+            scored.sort_by_key(|(distance, _)| *distance);
+            scored.into_iter().map(|(_, entry)| entry).collect()
+        }
+    }
+}
+
+/// Number of single character insertions, deletions or substitutions to turn `a` into `b`
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::config_for;
+    use std::fs;
+
+    fn entries(words: &[&str]) -> Vec<WordEntry> {
+        words
+            .iter()
+            .map(|w| WordEntry {
+                word: w.to_string(),
+                dictionaries: vec![String::from("authoritative")],
+            })
+            .collect()
+    }
+
+    fn words(entries: &[WordEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.word.as_str()).collect()
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("obsidian", "obsidian"), 0);
+    }
+
+    #[test]
+    fn test_search_modes() {
+        let all = entries(&["FitBit", "Obsidian", "fitness", "obsidian.md"]);
+
+        let found = search(
+            all.clone(),
+            &SearchMode::Substring {
+                text: String::from("fit"),
+                ignore_case: true,
+            },
+        );
+        assert_eq!(words(&found), vec!["FitBit", "fitness"]);
+
+        let found = search(
+            all.clone(),
+            &SearchMode::Regex(Regex::new(r"\.md$").unwrap()),
+        );
+        assert_eq!(words(&found), vec!["obsidian.md"]);
+
+        let found = search(
+            all,
+            &SearchMode::Fuzzy {
+                text: String::from("obsidain"),
+                max_distance: 2,
+            },
+        );
+        assert_eq!(words(&found), vec!["Obsidian"]);
+    }
+
+    #[test]
+    fn test_list_filters_by_dictionary() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("master.txt"), "alpha\nbeta\n")?;
+        fs::write(temp_dir.path().join("a.txt"), "beta\ngamma\n")?;
+        let config = config_for(
+            temp_dir.path(),
+            r#"
+            [dictionaries.a]
+            path = "{dir}/a.txt"
+            [filters]
+            remove = []
+            "#,
+        );
+
+        let all = list(&config, &[], &[])?;
+        assert_eq!(words(&all), vec!["alpha", "beta", "gamma"]);
+        assert_eq!(all[1].dictionaries, vec!["a", "authoritative"]);
+
+        let only_a = list(
+            &config,
+            &[String::from("a")],
+            &[String::from("authoritative")],
+        )?;
+        assert_eq!(words(&only_a), vec!["gamma"]);
+
+        let err = list(&config, &[String::from("nope")], &[]).unwrap_err();
+        assert!(matches!(err, Error::UnknownDictionary(_)));
+        Ok(())
+    }
+}
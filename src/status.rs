@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::sync::{self, load_authoritative, load_dictionary, Loaded};
+use crate::user_dictionary::get_hydrated_path_from_str;
+
+/// How a single configured dictionary compares to the words the next sync would write
+#[derive(Serialize, Debug)]
+pub struct DictionaryStatus {
+    pub name: String,
+    /// Expanded path to the dictionary file
+    pub path: String,
+    pub enabled: bool,
+    pub readonly: bool,
+    pub exists: bool,
+    /// Number of words in the dictionary
+    pub words: usize,
+    /// Words this dictionary has that the next sync would not write, e.g. words a filter removes
+    pub extra: usize,
+    /// Words the next sync would write that this dictionary does not have
+    pub missing: usize,
+    /// State of the checksum line; None if the file could not be read
+    pub checksum: Option<String>,
+    pub checksum_valid: bool,
+    /// Entries in the file that are not valid words, whatever `invalid_words` did with them
+    pub invalid: usize,
+    /// Why the file could not be read, if it exists but could not be read
+    pub error: Option<String>,
+    /// False if the next sync would change this dictionary or take words from it
+    pub in_sync: bool,
+}
+
+/// Compares every configured dictionary to the words the next sync would write (merged and filtered, the same as a
+/// dry run) without writing anything.
+///
+/// A dictionary is out of sync if it has words the sync would not write, lacks words it would write, its checksum
+/// line is not valid or it has entries that `invalid_words` trims or drops. Readonly dictionaries are never written
+/// so only their extra words count. A missing authoritative dictionary is out of sync since sync creates it.
+/// Disabled dictionaries and other missing files that sync would skip anyway are never out of sync.
+pub fn status(config: &Config) -> Result<Vec<DictionaryStatus>, Error> {
+    let target: HashSet<String> = sync::dry_run(config)?.words;
+
+    let mut loaded = Vec::new();
+    for (name, dictionary) in config.dictionaries.iter() {
+        let entry = if name == "authoritative" {
+            // Same as sync: a missing authoritative dictionary is created, whatever `create_if_missing` says
+            let path = config.get_authoritative_dictionary_path(false)?;
+            let exists = path.exists();
+            let ud = load_authoritative(config, path)?;
+            if exists {
+                Loaded::Existing(ud)
+            } else {
+                Loaded::New(ud)
+            }
+        } else {
+            load_dictionary(name, dictionary)?
+        };
+        loaded.push((name, dictionary, entry));
+    }
+
+    let mut statuses = Vec::new();
+    for (name, dictionary, entry) in loaded.iter() {
+        let path = get_hydrated_path_from_str(&dictionary.path)?;
+        let mut status = DictionaryStatus {
+            name: name.to_string(),
+            path: path.display().to_string(),
+            enabled: dictionary.enabled,
+            readonly: dictionary.readonly,
+            exists: path.exists(),
+            words: 0,
+            extra: 0,
+            missing: 0,
+            checksum: None,
+            checksum_valid: false,
+            invalid: 0,
+            error: None,
+            in_sync: true,
+        };
+        match entry {
+            Loaded::Disabled => {}
+            // Sync creates it
+            Loaded::New(_) => {
+                status.missing = target.len();
+                status.in_sync = false;
+            }
+            Loaded::Skipped(reason) => {
+                if status.exists {
+                    status.error = Some(reason.clone());
+                    status.in_sync = false;
+                }
+            }
+            Loaded::Existing(ud) => {
+                status.path = ud.path.display().to_string();
+                status.words = ud.words.len();
+                status.extra = ud.words.difference(&target).count();
+                status.missing = target.difference(&ud.words).count();
+                if let Some(checksum) = ud.checksum.as_ref() {
+                    status.checksum = Some(checksum.to_string());
+                    status.checksum_valid = checksum.is_valid();
+                }
+                status.invalid = ud.validation.invalid_count();
+                status.in_sync = if dictionary.readonly {
+                    status.extra == 0
+                } else {
+                    // Trimmed or dropped entries are gone from the file once it is rewritten
+                    status.extra == 0
+                        && status.missing == 0
+                        && status.checksum_valid
+                        && !ud.validation.changed_words()
+                };
+            }
+        }
+        statuses.push(status);
+    }
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::sync;
+    use crate::test_util::config_for;
+    use std::fs;
+
+    #[test]
+    fn test_status() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\n")?;
+        let config = config_for(
+            temp_dir.path(),
+            r#"
+            [dictionaries.a]
+            path = "{dir}/a.txt"
+            [dictionaries.gone]
+            path = "{dir}/gone.txt"
+            [dictionaries.ro]
+            path = "{dir}/ro.txt"
+            readonly = true
+            [filters]
+            remove = []
+            [backup]
+            enabled = false
+            "#,
+        );
+        sync(&config)?;
+        assert!(status(&config)?.iter().all(|s| s.in_sync));
+
+        // Hand edit `a` and give the readonly dictionary a subset of the words
+        fs::write(temp_dir.path().join("a.txt"), "alpha\nbeta\n")?;
+        fs::write(temp_dir.path().join("ro.txt"), "")?;
+        let statuses = status(&config)?;
+        let a = &statuses[0];
+        // `beta` is a new word the next sync keeps, not an extra one; only the checksum line is off
+        assert_eq!((a.words, a.extra, a.missing), (2, 0, 0));
+        assert!(!a.checksum_valid);
+        assert!(!a.in_sync);
+
+        let gone = &statuses[2];
+        assert!(!gone.exists);
+        assert!(gone.in_sync);
+
+        let ro = &statuses[3];
+        // The next sync writes `beta` everywhere
+        assert_eq!((ro.extra, ro.missing), (0, 2));
+        assert!(ro.in_sync);
+        let master = &statuses[1];
+        assert_eq!((master.extra, master.missing), (0, 1));
+        assert!(!master.in_sync);
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_compares_to_what_sync_writes() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\nTODO\n")?;
+        let config = config_for(
+            temp_dir.path(),
+            r#"
+            [dictionaries.a]
+            path = "{dir}/a.txt"
+            [filters]
+            remove = ["TODO"]
+            [backup]
+            enabled = false
+            "#,
+        );
+
+        // Nothing has been synced yet; the master would be created with `alpha` and `a` would lose `TODO`
+        let statuses = status(&config)?;
+        let master = &statuses[1];
+        assert!(!master.exists);
+        assert_eq!(master.missing, 1);
+        assert!(!master.in_sync);
+        let a = &statuses[0];
+        assert_eq!((a.words, a.extra, a.missing), (2, 1, 0));
+        assert!(!a.in_sync);
+
+        sync(&config)?;
+        assert!(status(&config)?.iter().all(|s| s.in_sync));
+        Ok(())
+    }
+}
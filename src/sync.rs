@@ -0,0 +1,968 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::backup::{self, BackupRun};
+use crate::config::{Config, Dictionary};
+use crate::diff::DictionaryDiff;
+use crate::error::Error;
+use crate::formats::Format;
+use crate::merge;
+use crate::user_dictionary::{get_hydrated_path_from_str, ChecksumStatus, UserDictionary};
+use crate::validation::EncodingErrorPolicy;
+
+/// What happened (or on a dry run, would happen) to a single configured dictionary
+#[derive(Debug)]
+pub enum SyncOutcome {
+    /// Dictionary was rewritten with the merged words. The diff may be empty if nothing changed.
+    Updated { path: PathBuf, diff: DictionaryDiff },
+    /// Dictionary did not exist and was created with the merged words (sorted)
+    Created { path: PathBuf, words: Vec<String> },
+    /// Dictionary was read but never written; the diff is how far it is from the merged words
+    Readonly { path: PathBuf, diff: DictionaryDiff },
+    /// Dictionary is disabled in the config file
+    Disabled,
+    /// Dictionary could not be read so it took no part in the sync
+    Skipped { reason: String },
+}
+
+impl fmt::Display for SyncOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncOutcome::Updated { path, diff } if diff.is_empty() => {
+                write!(f, "'{}' is already up to date", path.display())
+            }
+            SyncOutcome::Updated { path, diff } => {
+                // Diff ends with a newline of its own
+                write!(f, "'{}'\n{}", path.display(), diff.to_string().trim_end())
+            }
+            SyncOutcome::Created { path, words } => {
+                write!(f, "'{}' is new with {} words", path.display(), words.len())?;
+                for word in words {
+                    write!(f, "\n  + {}", word)?;
+                }
+                Ok(())
+            }
+            SyncOutcome::Readonly { path, .. } => {
+                write!(f, "'{}' is readonly; not written", path.display())
+            }
+            SyncOutcome::Disabled => write!(f, "is disabled"),
+            SyncOutcome::Skipped { reason } => write!(f, "is skipped; {}", reason),
+        }
+    }
+}
+
+/// Summary of a sync (or dry run) across every configured dictionary
+#[derive(Debug)]
+pub struct SyncReport {
+    /// True if nothing was written to disk
+    pub dry_run: bool,
+    /// The merged words every writable dictionary now has
+    pub words: HashSet<String>,
+    /// Name of the backup run holding the previous contents, if backups are enabled
+    pub backup_run: Option<String>,
+    /// One entry per configured dictionary, in alphabetical order of name (not the order of the config file)
+    pub dictionaries: Vec<(String, SyncOutcome)>,
+}
+
+/// The state of a single dictionary's checksum line as found by `verify()`
+#[derive(Debug)]
+pub enum VerifyOutcome {
+    /// File exists and was read; checksum says whether Obsidian will trust it
+    Checked {
+        path: PathBuf,
+        checksum: ChecksumStatus,
+    },
+    /// File does not exist. Not an error; sync skips these too.
+    NotFound { path: String },
+    /// File exists but could not be read
+    Unreadable(Error),
+}
+
+impl VerifyOutcome {
+    /// True if this dictionary would trip up Obsidian or could not be checked at all
+    pub fn is_inconsistent(&self) -> bool {
+        match self {
+            VerifyOutcome::Checked { checksum, .. } => !checksum.is_valid(),
+            VerifyOutcome::NotFound { .. } => false,
+            VerifyOutcome::Unreadable(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for VerifyOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyOutcome::Checked { path, checksum } => {
+                write!(f, "'{}' checksum is {}", path.display(), checksum)
+            }
+            VerifyOutcome::NotFound { path } => write!(f, "'{}' not found", path),
+            VerifyOutcome::Unreadable(err) => write!(f, "could not be read: {}", err),
+        }
+    }
+}
+
+/// Merges every configured dictionary and writes the result back to all of them
+pub fn sync(config: &Config) -> Result<SyncReport, Error> {
+    run(config, false)
+}
+
+/// Works out everything `sync()` would do without writing anything to disk
+pub fn dry_run(config: &Config) -> Result<SyncReport, Error> {
+    run(config, true)
+}
+
+/// Checks the checksum line of every enabled dictionary without changing anything
+pub fn verify(config: &Config) -> Vec<(String, VerifyOutcome)> {
+    config
+        .dictionaries
+        .iter()
+        .filter(|(_, dictionary)| dictionary.enabled)
+        .map(|(name, dictionary)| {
+            let outcome = match UserDictionary::new_from_file_path_with_options(
+                &dictionary.path,
+                &dictionary.format_options(),
+            ) {
+                Err(e) if e.is_not_found() => VerifyOutcome::NotFound {
+                    path: dictionary.path.clone(),
+                },
+                Err(e) => VerifyOutcome::Unreadable(e),
+                Ok(ud) => VerifyOutcome::Checked {
+                    path: ud.path,
+                    // Came from disk so there is always a checksum status
+                    checksum: ud.checksum.unwrap_or(ChecksumStatus::Missing),
+                },
+            };
+            (name.clone(), outcome)
+        })
+        .collect()
+}
+
+/// A configured dictionary after trying to load it
+pub(crate) enum Loaded {
+    Disabled,
+    Skipped(String),
+    /// Read from disk
+    Existing(UserDictionary),
+    /// Does not exist yet but `create_if_missing` is set
+    New(UserDictionary),
+}
+
+/// Reads a configured dictionary from disk.
+/// Only a path that can't be expanded (or, with `encoding_errors = "strict"`, a file that is not valid UTF-8) is an
+/// error; anything else that goes wrong skips the dictionary.
+pub(crate) fn load_dictionary(name: &str, dictionary: &Dictionary) -> Result<Loaded, Error> {
+    if !dictionary.enabled {
+        info!("Dictionary '{}' is disabled; skipping", name);
+        return Ok(Loaded::Disabled);
+    }
+    info!("Processing dictionary: {}", name);
+    let dict_path = dictionary.path.as_str();
+
+    debug!("dictionary '{}' is located at '{}'...", name, dict_path);
+    let options = dictionary.format_options();
+    match UserDictionary::new_from_file_path_with_options(dict_path, &options) {
+        Err(e) if e.is_not_found() && dictionary.create_if_missing => {
+            info!(
+                "Dictionary '{}' does not exist at '{}'; it will be created",
+                name, dict_path
+            );
+            let path = get_hydrated_path_from_str(dict_path)?;
+            Ok(Loaded::New(
+                UserDictionary::new(path, HashSet::new()).with_options(&options),
+            ))
+        }
+        // A path we can't even expand is a config problem, not a missing file
+        Err(e @ Error::PathExpansion { .. }) => Err(e),
+        // Strict mode would rather fail the run than sync without this dictionary's words
+        Err(e @ Error::Encoding { .. })
+            if options.encoding_errors == EncodingErrorPolicy::Strict =>
+        {
+            Err(e)
+        }
+        Err(e) => {
+            warn!("Could not parse dictionary from '{}': {}", dict_path, e);
+            Ok(Loaded::Skipped(e.to_string()))
+        }
+        Ok(ud) => {
+            debug!("user_dictionary: {:#?}", ud);
+            match ud.checksum.as_ref() {
+                Some(ChecksumStatus::Valid | ChecksumStatus::NotApplicable) => {}
+                Some(ChecksumStatus::Missing) | None => {
+                    info!("Dictionary '{}' has no checksum line yet", name)
+                }
+                Some(status) => warn!(
+                    "Dictionary '{}' checksum is {}; Obsidian will not trust it until it is rewritten",
+                    name, status
+                ),
+            }
+            Ok(Loaded::Existing(ud))
+        }
+    }
+}
+
+/// Copies each dictionary into a new backup run if backups are enabled and prunes old runs.
+/// Returns the name of the new run.
+pub(crate) fn backup_before_write<'a>(
+    config: &Config,
+    files: impl Iterator<Item = (&'a str, &'a UserDictionary)>,
+) -> Result<Option<String>, Error> {
+    if !config.backup.enabled {
+        return Ok(None);
+    }
+    let backup_dir = get_hydrated_path_from_str(&config.backup.dir)?;
+    let mut backup_run = BackupRun::new(&backup_dir)?;
+    for (name, ud) in files {
+        backup_run.snapshot(name, &ud.path, ud.format)?;
+    }
+    // The base snapshot and the list of synced dictionaries are rewritten too; restoring the dictionaries without
+    // them would have the next sync read the restored words as deletions or additions
+    let authoritative_path = config.get_authoritative_dictionary_path(false)?;
+    backup_run.snapshot(
+        "authoritative.base",
+        &merge::base_snapshot_path(&authoritative_path),
+        Format::Chromium,
+    )?;
+    backup_run.snapshot(
+        "authoritative.base.dictionaries",
+        &merge::synced_dictionaries_path(&authoritative_path),
+        Format::Chromium,
+    )?;
+    let run = backup_run.finish()?.run;
+    backup::prune(&backup_dir, config.backup.keep)?;
+    Ok(Some(run))
+}
+
+/// Runs the dictionary's `post_write` command, if it has one, after its file at `path` was written.
+/// The file is written either way so a failing command is only a warning.
+pub(crate) fn run_post_write(name: &str, dictionary: &Dictionary, path: &Path) {
+    let Some(command) = &dictionary.post_write else {
+        return;
+    };
+    debug!("Running post_write for '{}': {}", name, command);
+    // `sh -c <command> sh <path>` makes the path `$1` so it never has to be quoted into the command
+    match Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .arg("sh")
+        .arg(path)
+        .status()
+    {
+        Ok(status) if status.success() => info!("Ran post_write for '{}'", name),
+        Ok(status) => warn!("post_write for '{}' failed with {}", name, status),
+        Err(e) => warn!("Could not run post_write for '{}': {}", name, e),
+    }
+}
+
+/// Reads the authoritative dictionary from `path`, or starts an empty one if it does not exist yet
+pub(crate) fn load_authoritative(config: &Config, path: PathBuf) -> Result<UserDictionary, Error> {
+    let options = config.dictionaries["authoritative"].format_options();
+    if path.exists() {
+        UserDictionary::new_from_pathbuf_with_options(path, &options)
+    } else {
+        Ok(UserDictionary::new(path, HashSet::new()).with_options(&options))
+    }
+}
+
+/// Every entry `ud` was read with, including the ones its `invalid_words` policy trimmed or dropped.
+/// A word in the base snapshot that one dictionary's policy left out was not deleted from that dictionary; without
+/// this, dictionaries with different policies would delete each other's kept words.
+fn entries_as_read(ud: &UserDictionary) -> Cow<'_, HashSet<String>> {
+    let report = &ud.validation;
+    if report.trimmed.is_empty() && report.dropped.is_empty() {
+        return Cow::Borrowed(&ud.words);
+    }
+    let mut entries = ud.words.clone();
+    entries.extend(report.trimmed.iter().cloned());
+    entries.extend(report.dropped.iter().map(|(entry, _)| entry.clone()));
+    Cow::Owned(entries)
+}
+
+fn run(config: &Config, dry_run: bool) -> Result<SyncReport, Error> {
+    if dry_run {
+        info!("Dry run; nothing will be written to disk.");
+    }
+
+    // A missing authoritative dictionary is treated as empty and written along with the others. It takes no part
+    // in the merge so an empty file never reads as every word in the base snapshot having been deleted.
+    let authoritative_path = config.get_authoritative_dictionary_path(false)?;
+    let authoritative_exists = authoritative_path.exists();
+    let mut authoritative_dict = load_authoritative(config, authoritative_path)?;
+    debug!("authoritative_dict: {:#?}", authoritative_dict);
+    info!(
+        "Authoritative Dictionary has {} words",
+        authoritative_dict.words.len()
+    );
+
+    // The base snapshot is the word set as of the end of the last sync; it's how we tell deletions from additions
+    let base_path = merge::base_snapshot_path(&authoritative_dict.path);
+    let base_dict = if base_path.exists() {
+        match merge::read_base_snapshot(base_path.clone()) {
+            Ok(bd) => {
+                info!("Base snapshot has {} words", bd.words.len());
+                Some(bd)
+            }
+            Err(e) => {
+                warn!(
+                    "Could not read base snapshot '{}', deletions will not propagate this run: {}",
+                    base_path.display(),
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        info!("No base snapshot found; merging all dictionaries together.");
+        None
+    };
+    // Only a dictionary written by an earlier sync can be expected to have the base words; any other (e.g. one just
+    // added to the config) would otherwise read as every word in the base having been deleted from it
+    let synced_path = merge::synced_dictionaries_path(&authoritative_dict.path);
+    let mut synced = merge::read_synced_dictionaries(&synced_path).unwrap_or_else(|e| {
+        warn!(
+            "Could not read '{}', only the authoritative dictionary can propagate deletions this run: {}",
+            synced_path.display(),
+            e
+        );
+        HashSet::new()
+    });
+    if !authoritative_exists && base_dict.is_some() {
+        warn!(
+            "Authoritative dictionary '{}' is missing; rebuilding it from the other dictionaries",
+            authoritative_dict.path.display()
+        );
+    }
+
+    // The authoritative dictionary was loaded above; keep track of which of the others we found on disk, we'll have
+    // to write the combined authoritative list to these
+    let mut loaded: Vec<(&String, Loaded)> = Vec::new();
+    for (name, dictionary) in config.dictionaries.iter() {
+        if name != "authoritative" {
+            loaded.push((name, load_dictionary(name, dictionary)?));
+        }
+    }
+
+    // Only dictionaries that we could actually read take part in the merge; a missing file is not a deletion.
+    // The authoritative dictionary is written along with the base snapshot every run so it always counts as synced.
+    let mut synced_entries: Vec<Cow<HashSet<String>>> = Vec::new();
+    let mut additions_only: Vec<&HashSet<String>> = Vec::new();
+    let mut read_words: Vec<&HashSet<String>> = Vec::new();
+    if authoritative_exists {
+        synced_entries.push(entries_as_read(&authoritative_dict));
+        read_words.push(&authoritative_dict.words);
+    }
+    for (name, entry) in loaded.iter() {
+        if let Loaded::Existing(ud) = entry {
+            read_words.push(&ud.words);
+            if config.dictionaries[*name].readonly {
+                additions_only.push(&ud.words);
+            } else if synced.contains(&ud.path) {
+                synced_entries.push(entries_as_read(ud));
+            } else {
+                info!(
+                    "Dictionary '{}' has not been synced before; its words are only added this run",
+                    name
+                );
+                additions_only.push(&ud.words);
+            }
+        }
+    }
+    let synced_entries: Vec<&HashSet<String>> = synced_entries.iter().map(AsRef::as_ref).collect();
+    // Deletions are worked out from the entries as read but only words that made it through validation somewhere
+    // end up in the result
+    let mut merged = UserDictionary::new(
+        authoritative_dict.path.clone(),
+        merge::three_way_merge(
+            base_dict.as_ref().map(|bd| &bd.words),
+            &synced_entries,
+            &additions_only,
+        )
+        .into_iter()
+        .filter(|word| read_words.iter().any(|words| words.contains(word)))
+        .collect(),
+    );
+
+    // Some formats mark a removed word rather than leaving it out; that is a deletion even without a base snapshot.
+    // Readonly dictionaries only ever add words so their marks don't count.
+    let marked_deleted: HashSet<String> = loaded
+        .iter()
+        .filter_map(|(name, entry)| match entry {
+            Loaded::Existing(ud) if !config.dictionaries[*name].readonly => Some(&ud.deleted),
+            _ => None,
+        })
+        .chain([&authoritative_dict.deleted])
+        .flatten()
+        .cloned()
+        .collect();
+    let removed = merged.remove_words(&Vec::from_iter(marked_deleted));
+    info!("'{}' words were marked as removed", removed);
+
+    // After merging all words from all dictionaries, remove filtered words from the authoritative dictionary
+    debug!("config.filters.remove: {:#?}", config.filters.remove);
+    let removed = merged.remove_words_matching(|word| config.filters.matches(word));
+    info!("Filters removed '{}' words", removed);
+
+    // Keep a copy of everything we're about to overwrite
+    let backup_run_name = if dry_run {
+        None
+    } else {
+        let authoritative = authoritative_exists.then_some(("authoritative", &authoritative_dict));
+        backup_before_write(
+            config,
+            authoritative.into_iter().chain(loaded.iter().filter_map(
+                |(name, entry)| match entry {
+                    Loaded::Existing(ud) if !config.dictionaries[*name].readonly => {
+                        Some((name.as_str(), ud))
+                    }
+                    _ => None,
+                },
+            )),
+        )?
+    };
+
+    let authoritative_outcome = if authoritative_exists {
+        SyncOutcome::Updated {
+            path: authoritative_dict.path.clone(),
+            diff: DictionaryDiff::new(&authoritative_dict, &merged),
+        }
+    } else {
+        SyncOutcome::Created {
+            path: authoritative_dict.path.clone(),
+            words: merged.get_sorted_words().into_iter().cloned().collect(),
+        }
+    };
+    if !dry_run {
+        // Write the authoritative dictionary to disk
+        authoritative_dict.set_words(merged.words.clone());
+        debug!("authoritative_dict => '{}' ", &authoritative_dict);
+        authoritative_dict.write_to_disk()?;
+        run_post_write(
+            "authoritative",
+            &config.dictionaries["authoritative"],
+            &authoritative_dict.path,
+        );
+    }
+
+    // Iterate through the dictionary file(s) we did find on disk and write the authoritative dictionary to them
+    let mut dictionaries = vec![(String::from("authoritative"), authoritative_outcome)];
+    for (name, entry) in loaded {
+        let outcome = match entry {
+            Loaded::Disabled => SyncOutcome::Disabled,
+            Loaded::Skipped(reason) => SyncOutcome::Skipped { reason },
+            Loaded::Existing(ud) if config.dictionaries[name].readonly => {
+                debug!("Dictionary '{}' is readonly; not writing", name);
+                SyncOutcome::Readonly {
+                    diff: DictionaryDiff::new(&ud, &merged),
+                    path: ud.path,
+                }
+            }
+            Loaded::Existing(mut ud) => {
+                let diff = DictionaryDiff::new(&ud, &merged);
+                if !dry_run {
+                    // TODO: Is there a way to do this w/o clone()? At this point in code flow, the authoritative
+                    // dictionary is fixed and will not change.
+                    ud.set_words(merged.words.clone());
+                    ud.write_to_disk()?;
+                    run_post_write(name, &config.dictionaries[name], &ud.path);
+                    synced.insert(ud.path.clone());
+                }
+                SyncOutcome::Updated {
+                    path: ud.path,
+                    diff,
+                }
+            }
+            Loaded::New(mut ud) => {
+                if !dry_run {
+                    ud.set_words(merged.words.clone());
+                    ud.write_to_disk()?;
+                    run_post_write(name, &config.dictionaries[name], &ud.path);
+                    // Now that it exists, record it the way it is keyed when it is read back
+                    synced.insert(ud.path.canonicalize()?);
+                }
+                SyncOutcome::Created {
+                    path: ud.path,
+                    words: merged.get_sorted_words().into_iter().cloned().collect(),
+                }
+            }
+        };
+        dictionaries.push((name.clone(), outcome));
+    }
+    if !dry_run {
+        // Record what everything looks like now so the next run can detect deletions. Only done once every dictionary
+        // was written; a base with words some dictionary never got would read as those words being deleted from it.
+        let mut base_dict = UserDictionary::new(base_path, merged.words.clone());
+        base_dict.write_to_disk()?;
+        merge::write_synced_dictionaries(&synced_path, &synced).map_err(|source| Error::Write {
+            path: synced_path.clone(),
+            source,
+        })?;
+    }
+    // Back to alphabetical order of name now that the authoritative dictionary is in the list
+    dictionaries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(SyncReport {
+        dry_run,
+        words: merged.words,
+        backup_run: backup_run_name,
+        dictionaries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::config_for;
+    use std::fs;
+
+    /// `a`, `b` and `gone` (which doesn't exist) next to the master, with backups in `backups`
+    const CONFIG: &str = r#"
+        [dictionaries.a]
+        path = "{dir}/a.txt"
+        [dictionaries.b]
+        path = "{dir}/b.txt"
+        [dictionaries.gone]
+        path = "{dir}/gone.txt"
+        [filters]
+        remove = ["aa"]
+        [backup]
+        dir = "{dir}/backups"
+    "#;
+
+    #[test]
+    fn test_sync_merges_and_writes() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\naa\n")?;
+        fs::write(temp_dir.path().join("b.txt"), "beta\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+
+        let report = sync(&config)?;
+        assert!(!report.dry_run);
+        assert_eq!(
+            report.words,
+            HashSet::from_iter(["alpha".to_string(), "beta".to_string()])
+        );
+        assert!(report.backup_run.is_some());
+
+        let outcomes: Vec<&str> = report
+            .dictionaries
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect();
+        assert_eq!(outcomes, vec!["a", "authoritative", "b", "gone"]);
+        assert!(matches!(
+            &report.dictionaries[1].1,
+            SyncOutcome::Created { words, .. } if words == &["alpha", "beta"]
+        ));
+        match &report.dictionaries[2].1 {
+            SyncOutcome::Updated { diff, .. } => {
+                assert_eq!(diff.added, vec!["alpha"]);
+                // Hand written file without a checksum line
+                assert_eq!(diff.old_checksum, None);
+            }
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+        assert!(matches!(
+            report.dictionaries[3].1,
+            SyncOutcome::Skipped { .. }
+        ));
+
+        let b = UserDictionary::new_from_pathbuf(temp_dir.path().join("b.txt"))?;
+        assert_eq!(b.words, report.words);
+        assert_eq!(b.checksum, Some(ChecksumStatus::Valid));
+
+        // The checksum lines that were written are the ones a dry run expects to write
+        for (name, outcome) in dry_run(&config)?.dictionaries {
+            if let SyncOutcome::Updated { diff, .. } = outcome {
+                assert!(diff.is_empty(), "{} is not up to date: {:?}", name, diff);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run_writes_nothing() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\n")?;
+        fs::write(temp_dir.path().join("b.txt"), "beta\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+
+        let report = dry_run(&config)?;
+        assert!(report.dry_run);
+        assert!(report.backup_run.is_none());
+        match &report.dictionaries[0].1 {
+            SyncOutcome::Updated { diff, .. } => assert_eq!(diff.added, vec!["beta"]),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        assert!(!temp_dir.path().join("master.txt").exists());
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("a.txt"))?,
+            "alpha\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+        sync(&config)?;
+        fs::write(temp_dir.path().join("b.txt"), "hand edited\n")?;
+
+        let results = verify(&config);
+        let inconsistent: Vec<&str> = results
+            .iter()
+            .filter(|(_, outcome)| outcome.is_inconsistent())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(inconsistent, vec!["b"]);
+        assert!(matches!(results[3].1, VerifyOutcome::NotFound { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_invalid_words() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(
+            temp_dir.path().join("a.txt"),
+            "alpha\n  padded \n\ntwo words\n",
+        )?;
+        fs::write(
+            temp_dir.path().join("rejected.txt"),
+            "beta\nbad\u{0}merge\n",
+        )?;
+        let config = config_for(temp_dir.path(), &[CONFIG, "[dictionaries.rejected]\npath = \"{dir}/rejected.txt\"\ninvalid_words = \"reject\""].concat());
+
+        let report = sync(&config)?;
+        // `a` is trimmed by default; `rejected` takes no part in the merge
+        assert_eq!(
+            report.words,
+            HashSet::from_iter(["alpha".to_string(), "padded".to_string()])
+        );
+        assert!(matches!(
+            report
+                .dictionaries
+                .iter()
+                .find(|(n, _)| n == "rejected")
+                .unwrap()
+                .1,
+            SyncOutcome::Skipped { .. }
+        ));
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("rejected.txt"))?,
+            "beta\nbad\u{0}merge\n"
+        );
+
+        let err = UserDictionary::new_from_pathbuf_with_options(
+            temp_dir.path().join("rejected.txt"),
+            &config.dictionaries["rejected"].format_options(),
+        )
+        .unwrap_err();
+        assert_eq!(err.exit_code(), 11);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_invalid_word_policies_do_not_delete() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(
+            temp_dir.path().join("kept.txt"),
+            "alpha\ntwo words\n padded\n",
+        )?;
+        fs::write(temp_dir.path().join("dropping.txt"), "alpha\n")?;
+        let config = config_for(
+            temp_dir.path(),
+            &[
+                CONFIG,
+                "[dictionaries.kept]\npath = \"{dir}/kept.txt\"\ninvalid_words = \"keep\"\n\
+                 [dictionaries.dropping]\npath = \"{dir}/dropping.txt\"\ninvalid_words = \"drop\"",
+            ]
+            .concat(),
+        );
+        let words: HashSet<String> = HashSet::from_iter(
+            ["alpha", "two words", " padded"]
+                .iter()
+                .map(|w| w.to_string()),
+        );
+        assert_eq!(sync(&config)?.words, words);
+
+        // `dropping` (and the authoritative dictionary, which trims) now have the kept entries but leave them out
+        // when they are read; that is not a deletion. Trimming ` padded` does make `padded` a word of its own.
+        let report = sync(&config)?;
+        assert!(report.words.is_superset(&words), "{:?}", report.words);
+        assert!(report.words.contains("padded"));
+        let kept = UserDictionary::new_from_pathbuf_with_options(
+            temp_dir.path().join("kept.txt"),
+            &config.dictionaries["kept"].format_options(),
+        )?;
+        assert_eq!(kept.words, report.words);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_encoding_errors() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), b"alpha\ncaf\xe9\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+        let report = sync(&config)?;
+        assert_eq!(report.words, HashSet::from_iter(["alpha".to_string()]));
+
+        fs::write(temp_dir.path().join("b.txt"), b"beta\n\xff\n")?;
+        let config = config_for(
+            temp_dir.path(),
+            &[
+                CONFIG,
+                "[dictionaries.c]\npath = \"{dir}/b.txt\"\nencoding_errors = \"strict\"",
+            ]
+            .concat(),
+        );
+        let err = sync(&config).unwrap_err();
+        assert_eq!(err.exit_code(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_hunspell() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\n")?;
+        fs::write(temp_dir.path().join("en_US.dic"), "2\nbeta/MS\ngamma\n")?;
+        let config = config_for(
+            temp_dir.path(),
+            &[
+                CONFIG,
+                "[dictionaries.hunspell]\npath = \"{dir}/en_US.dic\"\nformat = \"hunspell\"",
+            ]
+            .concat(),
+        );
+
+        let report = sync(&config)?;
+        assert_eq!(report.words.len(), 3);
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("en_US.dic"))?,
+            // The flags of a word already in the file are kept
+            "3\nalpha\nbeta/MS\ngamma\n"
+        );
+        assert!(temp_dir.path().join("en_US.aff").exists());
+        // Nothing to check for a format without a checksum line
+        assert!(!verify(&config).iter().any(|(_, o)| o.is_inconsistent()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_new_empty_dictionary_deletes_nothing() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\nbeta\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+        sync(&config)?;
+
+        // A dictionary added to the config later has none of the base words but they were never deleted from it
+        fs::write(temp_dir.path().join("slack.txt"), "")?;
+        let config = config_for(
+            temp_dir.path(),
+            &[CONFIG, "[dictionaries.slack]\npath = \"{dir}/slack.txt\""].concat(),
+        );
+        let words = HashSet::from_iter(["alpha".to_string(), "beta".to_string()]);
+        assert_eq!(sync(&config)?.words, words);
+        let slack = UserDictionary::new_from_pathbuf(temp_dir.path().join("slack.txt"))?;
+        assert_eq!(slack.words, words);
+
+        // Once synced it propagates deletions like any other dictionary
+        fs::write(temp_dir.path().join("slack.txt"), "alpha\n")?;
+        assert_eq!(
+            sync(&config)?.words,
+            HashSet::from_iter(["alpha".to_string()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_discovered_dictionary_deletes_nothing() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\n")?;
+        let mut config = config_for(temp_dir.path(), CONFIG);
+        sync(&config)?;
+
+        // Obsidian was just installed so its dictionary is empty
+        let env = crate::discovery::DiscoveryEnv {
+            home: temp_dir.path().to_path_buf(),
+            xdg_config_home: None,
+        };
+        let obsidian = temp_dir
+            .path()
+            .join(".config/obsidian/Custom Dictionary.txt");
+        fs::create_dir_all(obsidian.parent().unwrap())?;
+        fs::write(&obsidian, "")?;
+        assert_eq!(config.add_discovered(&env), vec!["obsidian"]);
+
+        let report = sync(&config)?;
+        assert_eq!(report.words, HashSet::from_iter(["alpha".to_string()]));
+        assert_eq!(
+            UserDictionary::new_from_pathbuf(obsidian)?.words,
+            report.words
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_failed_write_keeps_base() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+        sync(&config)?;
+
+        fs::write(temp_dir.path().join("b.txt"), "beta\n")?;
+        let config = config_for(temp_dir.path(), &[CONFIG, "[dictionaries.broken]\npath = \"{dir}/missing_dir/broken.txt\"\ncreate_if_missing = true"].concat());
+        assert_eq!(sync(&config).unwrap_err().exit_code(), 7);
+        // `broken` never got `beta` so the base must not claim every dictionary has it
+        let base = UserDictionary::new_from_pathbuf(temp_dir.path().join("master.txt.base"))?;
+        assert_eq!(base.words, HashSet::from_iter(["alpha".to_string()]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_backs_up_base() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+        sync(&config)?;
+        let base_path = temp_dir.path().join("master.txt.base");
+        let base_before = fs::read(&base_path)?;
+
+        fs::write(temp_dir.path().join("a.txt"), "alpha\nbeta\n")?;
+        let run = sync(&config)?.backup_run.unwrap();
+        assert_ne!(fs::read(&base_path)?, base_before);
+
+        let backup_dir = temp_dir.path().join("backups");
+        let restored: Vec<String> = backup::restore(&backup_dir, &run, None, 0)?
+            .into_iter()
+            .map(|file| file.name)
+            .collect();
+        assert_eq!(
+            restored,
+            vec![
+                "authoritative",
+                "a",
+                "authoritative.base",
+                "authoritative.base.dictionaries"
+            ]
+        );
+        assert_eq!(fs::read(&base_path)?, base_before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_rebuilds_missing_authoritative() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\nbeta\n")?;
+        fs::write(temp_dir.path().join("b.txt"), "beta\n")?;
+        let config = config_for(temp_dir.path(), CONFIG);
+        sync(&config)?;
+        assert!(temp_dir.path().join("master.txt.base").exists());
+
+        // An empty master would otherwise look like every word was deleted from it
+        fs::remove_file(temp_dir.path().join("master.txt"))?;
+        let report = sync(&config)?;
+        let words = HashSet::from_iter(["alpha".to_string(), "beta".to_string()]);
+        assert_eq!(report.words, words);
+        assert!(matches!(
+            report.dictionaries[1].1,
+            SyncOutcome::Created { .. }
+        ));
+        let master = UserDictionary::new_from_pathbuf(temp_dir.path().join("master.txt"))?;
+        assert_eq!(master.words, words);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sync_writes_authoritative_once() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("master.txt"), "alpha\n")?;
+        fs::write(temp_dir.path().join("a.txt"), "beta\n")?;
+        let config = Config::parse(&format!(
+            r#"
+            [dictionaries.authoritative]
+            path = "{dir}/master.txt"
+            post_write = 'echo written >> "$1.log"'
+            [dictionaries.a]
+            path = "{dir}/a.txt"
+            [filters]
+            remove = []
+            [backup]
+            enabled = false
+            "#,
+            dir = temp_dir.path().display(),
+        ))?;
+
+        let report = sync(&config)?;
+        let names: Vec<&str> = report
+            .dictionaries
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "authoritative"]);
+        match &report.dictionaries[1].1 {
+            SyncOutcome::Updated { diff, .. } => {
+                assert_eq!(diff.added, vec!["beta"]);
+                assert!(diff.removed.is_empty());
+            }
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("master.txt.log"))?,
+            "written\n"
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sync_vim_deletions_and_post_write() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\nbeta\n")?;
+        fs::write(
+            temp_dir.path().join("en.utf-8.add"),
+            "#alpha\nbeta\ngamma\n",
+        )?;
+        let config = config_for(temp_dir.path(), &[CONFIG, "[dictionaries.vim]\npath = \"{dir}/en.utf-8.add\"\nformat = \"vim\"\npost_write = 'cp \"$1\" \"$1.spl\"'"].concat());
+
+        // No base snapshot yet but the commented out word is still a deletion
+        let report = sync(&config)?;
+        assert_eq!(
+            report.words,
+            HashSet::from_iter(["beta".to_string(), "gamma".to_string()])
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("en.utf-8.add"))?,
+            "beta\ngamma\n"
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("en.utf-8.add.spl"))?,
+            "beta\ngamma\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_readonly_vim_deletes_nothing() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\nbeta\n")?;
+        let vim = "#alpha\ngamma\n";
+        fs::write(temp_dir.path().join("en.utf-8.add"), vim)?;
+        let config = config_for(temp_dir.path(), &[CONFIG, "[dictionaries.vim]\npath = \"{dir}/en.utf-8.add\"\nformat = \"vim\"\nreadonly = true"].concat());
+
+        // Its words are still added but its commented out word stays everywhere else
+        let report = sync(&config)?;
+        assert_eq!(
+            report.words,
+            HashSet::from_iter(["alpha", "beta", "gamma"].map(String::from))
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("en.utf-8.add"))?,
+            vim
+        );
+        Ok(())
+    }
+}
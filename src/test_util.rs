@@ -0,0 +1,23 @@
+//! Helpers shared by the unit tests of every module
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::config::Config;
+
+/// Owned set of `list`, to compare parsed or merged words against
+pub fn words(list: &[&str]) -> HashSet<String> {
+    list.iter().map(|w| w.to_string()).collect()
+}
+
+/// Parses a config whose authoritative dictionary is `master.txt` in `dir`. `rest` is the remainder of the file;
+/// every `{dir}` in it is replaced with `dir`.
+pub fn config_for(dir: &Path, rest: &str) -> Config {
+    let dir = dir.display().to_string();
+    Config::parse(&format!(
+        "[dictionaries.authoritative]\npath = \"{}/master.txt\"\n{}",
+        dir,
+        rest.replace("{dir}", &dir)
+    ))
+    .unwrap()
+}
@@ -1,25 +1,96 @@
+use std::borrow::Cow;
 use std::collections::HashSet;
 use std::convert::AsRef;
 use std::fmt;
 use std::fs;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io;
 
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
-const CHECKSUM_PRELUDE: &str = "checksum_v1 = ";
+use crate::error::Error;
+use crate::formats::{chromium, Format, FormatError, FormatOptions, Metadata, TextLayout};
+use crate::validation::{self, EncodingErrorPolicy, ValidationReport};
 
 #[derive(Debug)]
 /// Represents a checksummed list of words used by Electron based apps for custom spell checking.
 pub struct UserDictionary {
     // Custom dictionary is just a text file; one word per line
-    pub path: Option<PathBuf>,
-    pub words: Option<HashSet<String>>,
+    pub path: PathBuf,
+    pub words: HashSet<String>,
 
     // And the last line of the file is a checksum of all the words
     // TODO: is there a way to make this public but also read only?
-    pub hash: Option<md5::Digest>,
+    pub hash: md5::Digest,
+
+    // State of the checksum line when the file was read. None if the dictionary did not come from disk.
+    pub checksum: Option<ChecksumStatus>,
+
+    // Digest on the checksum line when the file was read, if it had exactly one
+    pub stored_checksum: Option<String>,
+
+    // Layout of the file on disk; the hash is only written for formats that have a checksum line
+    pub format: Format,
+
+    // Language tag for formats that record one. Read from the file unless the config gives one.
+    pub lang: Option<String>,
+
+    // Where the words are in formats that nest them, e.g. `cSpell.words`
+    pub key: Option<String>,
+
+    // Words the file marks as removed rather than leaving out, e.g. commented out in a Vim spell file
+    pub deleted: HashSet<String>,
+
+    // Line ending and BOM to write. Read from the file unless the config gives them.
+    pub layout: TextLayout,
+
+    // Entries in the file that were not valid words and what was done about them
+    pub validation: ValidationReport,
+
+    // Where lines that are not valid UTF-8 are moved to when the dictionary is written. None to let them go.
+    pub quarantine: Option<PathBuf>,
+}
+
+/// What we found when comparing a dictionary file's checksum line to the words in the file.
+/// Obsidian (well, Chromium) will throw away a dictionary file that does not have exactly one valid checksum line.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ChecksumStatus {
+    /// Exactly one checksum line and it matches the words
+    Valid,
+    /// Exactly one checksum line, but it does not match the words; likely edited by hand
+    Mismatched { stored: String, expected: String },
+    /// No checksum line at all
+    Missing,
+    /// More than one checksum line
+    Multiple(usize),
+    /// The dictionary's format has no checksum line
+    #[default]
+    NotApplicable,
+}
+
+impl ChecksumStatus {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, ChecksumStatus::Valid | ChecksumStatus::NotApplicable)
+    }
+}
+
+impl fmt::Display for ChecksumStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChecksumStatus::Valid => write!(f, "valid"),
+            ChecksumStatus::Mismatched { stored, expected } => {
+                write!(
+                    f,
+                    "mismatched (stored '{}', expected '{}')",
+                    stored, expected
+                )
+            }
+            ChecksumStatus::Missing => write!(f, "missing"),
+            ChecksumStatus::Multiple(count) => write!(f, "{} checksum lines", count),
+            ChecksumStatus::NotApplicable => write!(f, "not used by this format"),
+        }
+    }
 }
 
 impl AsRef<UserDictionary> for UserDictionary {
@@ -33,109 +104,161 @@ impl fmt::Display for UserDictionary {
         write!(
             f,
             "UserDictionary {{ path: {:?}, word count: {:?}, hash: {:?} }}",
-            self.path.as_ref().unwrap().to_str().unwrap(),
+            self.path.display(),
             // Dumping possibly hundreds of words to the log is not helpful; can use debug! for that if needed
-            self.words.as_ref().unwrap().len(),
-            format!("{:?}", self.hash.unwrap())
+            self.words.len(),
+            format!("{:?}", self.hash)
         )
     }
 }
 
 impl UserDictionary {
+    /// Create a new UserDictionary from a set of words that will be written to `path`.
+    /// Nothing is read from or written to disk until `write_to_disk()` is called.
+    pub fn new(path: PathBuf, words: HashSet<String>) -> Self {
+        UserDictionary {
+            path,
+            hash: calculate_hash_digest(&words),
+            words,
+            checksum: None,
+            stored_checksum: None,
+            format: Format::default(),
+            lang: None,
+            key: None,
+            deleted: HashSet::new(),
+            layout: TextLayout::default(),
+            validation: ValidationReport::default(),
+            quarantine: None,
+        }
+    }
+
+    /// Sets the format (and its settings) the dictionary will be written in
+    pub fn with_options(mut self, options: &FormatOptions) -> Self {
+        self.format = options.format;
+        self.lang = options.lang.clone();
+        self.key = options.key.clone();
+        self.layout = TextLayout {
+            line_ending: options.line_ending.unwrap_or(self.layout.line_ending),
+            bom: options.bom.unwrap_or(self.layout.bom),
+        };
+        self
+    }
+
     /// Create a new UserDictionary from an existing file on disk
     /// Return Error if the file path can't be fully canonicalized
-    pub fn new_from_file_path(dict_file: &str) -> Result<Self, io::Error> {
+    pub fn new_from_file_path(dict_file: &str) -> Result<Self, Error> {
+        Self::new_from_file_path_with_options(dict_file, &FormatOptions::default())
+    }
+
+    /// Like `new_from_file_path()` but for a file in the given format
+    pub fn new_from_file_path_with_options(
+        dict_file: &str,
+        options: &FormatOptions,
+    ) -> Result<Self, Error> {
         // User given string goes in fully expanded PathBuf comes out
         let dict_file_path = get_hydrated_path_from_str(dict_file)?;
-        // And then resolve to fqdn path. This will fail if the file does not exist.
+        Self::from_hydrated_path(dict_file_path, options)
+    }
 
-        let dict_file_path = dict_file_path.as_path().canonicalize()?;
-        // get_words_from_file will raise Error if the file can't be found but
-        // we _just_ checked that with canonicalize()? so we don't need to handle the same
-        // error condition ... twice, back to back.
-        let filtered_words = get_words_from_file(&dict_file_path)?;
-        // Return UserDictionary with the hashed words
-        Ok(UserDictionary {
-            path: Some(dict_file_path),
-            hash: Some(calculate_hash_digest(&filtered_words)),
-            words: Some(filtered_words),
-        })
+    pub fn new_from_pathbuf(dict_file: PathBuf) -> Result<Self, Error> {
+        Self::new_from_pathbuf_with_options(dict_file, &FormatOptions::default())
     }
 
-    pub fn new_from_pathbuf(dict_file: PathBuf) -> Result<Self, io::Error> {
+    /// Like `new_from_pathbuf()` but for a file in the given format
+    pub fn new_from_pathbuf_with_options(
+        dict_file: PathBuf,
+        options: &FormatOptions,
+    ) -> Result<Self, Error> {
         let dict_file_path = get_hydrated_path_from_pathbuf(&dict_file)?;
+        Self::from_hydrated_path(dict_file_path, options)
+    }
 
+    fn from_hydrated_path(dict_file_path: PathBuf, options: &FormatOptions) -> Result<Self, Error> {
+        // And then resolve to fqdn path. This will fail if the file does not exist.
         let dict_file_path = dict_file_path.as_path().canonicalize()?;
         // get_words_from_file will raise Error if the file can't be found but
         // we _just_ checked that with canonicalize()? so we don't need to handle the same
         // error condition ... twice, back to back.
-        let filtered_words = get_words_from_file(&dict_file_path)?;
+        let (words, metadata, validation) = get_words_from_file(&dict_file_path, options)?;
+        let quarantine = (options.encoding_errors == EncodingErrorPolicy::Quarantine)
+            .then(|| quarantine_path(&dict_file_path));
         // Return UserDictionary with the hashed words
         Ok(UserDictionary {
-            path: Some(dict_file_path),
-            hash: Some(calculate_hash_digest(&filtered_words)),
-            words: Some(filtered_words),
+            path: dict_file_path,
+            hash: calculate_hash_digest(&words),
+            words,
+            checksum: Some(metadata.checksum),
+            stored_checksum: metadata.stored_checksum,
+            format: options.format,
+            // The config has the final say
+            lang: options.lang.clone().or(metadata.lang),
+            key: options.key.clone(),
+            deleted: metadata.deleted,
+            layout: TextLayout {
+                line_ending: options.line_ending.unwrap_or(metadata.layout.line_ending),
+                bom: options.bom.unwrap_or(metadata.layout.bom),
+            },
+            validation,
+            quarantine,
         })
     }
 
-    /// Adds words to the dictionary and recalculates the hash
-    pub fn add_words(&mut self, words: HashSet<String>) {
-        // TODO: don't forget to remove filter words
-        match &self.words {
-            None => {
-                self.words = Some(words);
-            }
-            Some(_) => {
-                self.words = Some(
-                    self.words
-                        .as_ref()
-                        .unwrap()
-                        .union(&words)
-                        .cloned()
-                        .collect(),
-                );
-            }
-        }
-        // In either case, update the hash
-        self.hash = Some(calculate_hash_digest(self.words.as_ref().unwrap()));
+    /// Adds words to the dictionary and recalculates the hash.
+    /// Returns how many of them were not already in the dictionary.
+    pub fn add_words(&mut self, words: &[String]) -> usize {
+        let before = self.words.len();
+        self.words.extend(words.iter().cloned());
+        self.hash = calculate_hash_digest(&self.words);
+        self.words.len() - before
     }
 
-    /// Removes words from the dictionary and recalculates the hash
-    pub fn remove_words(&mut self, words: HashSet<String>) {
-        debug!(
-            "Attempting removal of '{}' words from dictionary.",
-            words.len()
-        );
-        // TODO: don't forget to remove filter words
-        match &self.words {
-            None => {
-                warn!("No words to remove from dictionary!");
-                return;
-            }
-            Some(_) => {
-                self.words = Some(
-                    self.words
-                        .as_ref()
-                        .unwrap()
-                        .difference(&words)
-                        .cloned()
-                        .collect(),
-                );
-            }
+    /// Removes words from the dictionary and recalculates the hash.
+    /// Returns how many of them were actually in the dictionary.
+    pub fn remove_words(&mut self, words: &[String]) -> usize {
+        self.remove_words_matching(|word| words.iter().any(|w| w == word))
+    }
+
+    /// Removes every word `matches` returns true for and recalculates the hash.
+    /// Returns how many words were removed.
+    pub fn remove_words_matching<F: Fn(&str) -> bool>(&mut self, matches: F) -> usize {
+        let before = self.words.len();
+        self.words.retain(|word| !matches(word));
+        self.hash = calculate_hash_digest(&self.words);
+        before - self.words.len()
+    }
+
+    /// The format settings the dictionary is read and written with
+    pub fn format_options(&self) -> FormatOptions {
+        FormatOptions {
+            format: self.format,
+            lang: self.lang.clone(),
+            key: self.key.clone(),
+            line_ending: Some(self.layout.line_ending),
+            bom: Some(self.layout.bom),
+            ..FormatOptions::default()
         }
-        // In either case, update the hash
-        self.hash = Some(calculate_hash_digest(self.words.as_ref().unwrap()));
     }
 
     /// Set the words in the dictionary and recalculate the hash
     pub fn set_words(&mut self, words: HashSet<String>) {
-        self.words = Some(words);
-        self.hash = Some(calculate_hash_digest(self.words.as_ref().unwrap()));
+        self.hash = calculate_hash_digest(&words);
+        self.words = words;
+    }
+
+    /// Digest on the checksum line that writing `words` to this dictionary would give it, in its format and layout.
+    /// None for formats without a checksum line.
+    pub fn checksum_for(&self, words: &HashSet<String>) -> Option<String> {
+        if !self.format.has_checksum() {
+            return None;
+        }
+        let mut sorted = words.iter().collect::<Vec<_>>();
+        sorted.sort();
+        Some(chromium::checksum(&sorted, &self.layout))
     }
 
-    /// Get the unique words,, sorted
-    fn get_sorted_words(&self) -> Vec<&String> {
-        let mut words = self.words.as_ref().unwrap().iter().collect::<Vec<_>>();
+    /// Get the unique words, sorted
+    pub fn get_sorted_words(&self) -> Vec<&String> {
+        let mut words = self.words.iter().collect::<Vec<_>>();
         words.sort();
         words
     }
@@ -144,34 +267,97 @@ impl UserDictionary {
     /// Creates the file if it doesn't exist.
     /// Overwrites the file if it does exist.
     /// Returns an error if the file can't be written to.
-    pub fn write_to_disk(&mut self) -> Result<(), io::Error> {
-        // Get cannon file path, create if it doesn't exist
-        let canonical_path_result = canonicalize_or_create(self.path.as_ref().unwrap()).ok();
-        debug!("Writing dictionary to '{:?}'", canonical_path_result);
-
-        let dict_file_path = self.path.as_ref().unwrap().canonicalize()?;
-        debug!(
-            "Writing dictionary to '{}'",
-            dict_file_path.to_str().unwrap()
+    pub fn write_to_disk(&mut self) -> Result<(), Error> {
+        self.quarantine_undecodable()?;
+        self.write_atomically().map_err(|source| Error::Write {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    /// Appends the lines that are not valid UTF-8 to the quarantine file so rewriting the dictionary doesn't lose
+    /// them. Only done once; writing the dictionary again does not add them again.
+    fn quarantine_undecodable(&mut self) -> Result<(), Error> {
+        let Some(quarantine) = self.quarantine.take() else {
+            return Ok(());
+        };
+        let lines = &self.validation.undecodable;
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let append = || -> io::Result<()> {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&quarantine)?;
+            for line in lines {
+                file.write_all(&line.bytes)?;
+                file.write_all(b"\n")?;
+            }
+            file.sync_all()
+        };
+        append().map_err(|source| Error::Write {
+            path: quarantine.clone(),
+            source,
+        })?;
+        info!(
+            "Moved '{}' lines that are not valid UTF-8 to '{}'",
+            lines.len(),
+            quarantine.display()
         );
+        Ok(())
+    }
+
+    fn write_atomically(&self) -> Result<(), io::Error> {
+        // Get cannon file path, create if it doesn't exist.
+        // Canonical path means that if the dictionary is a symlink, we replace the file it points to rather than the link.
+        let dict_file_path = canonicalize_or_create(&self.path)?;
+        debug!("Writing dictionary to '{}'", dict_file_path.display());
 
         // Internally, we're using the words as a HashSet for uniqueness
         let words = self.get_sorted_words();
         debug!("Writing '{:#?}' words to disk...", words.len());
-        // Add the checksum line to the end of the file
-        // TODO: implement Display for the hash?
-        let checksum_line = format!("{}{:#?}", CHECKSUM_PRELUDE, self.hash.as_ref().unwrap());
-
-        // Write the words to the file
-        let mut file = File::create(&dict_file_path)?;
-        for word in words {
-            file.write_all(word.as_bytes())?;
-            file.write_all(b"\n")?;
+        let format = self.format_options().dictionary_format();
+        // Formats that only own part of the file need what is there now
+        let existing = fs::read(&dict_file_path)?;
+        let metadata = Metadata {
+            lang: self.lang.clone(),
+            layout: self.layout,
+            ..Metadata::default()
+        };
+        let content = format.serialize(&dict_file_path, &words, &metadata, &existing)?;
+
+        // Write to a temp file next to the dictionary and then rename it over the dictionary.
+        // If we die part way through, the dictionary is left untouched rather than truncated.
+        // The temp file must be in the same directory so the rename is atomic (same file system).
+        let dict_dir = dict_file_path.parent().ok_or(io::Error::other(format!(
+            "'{}' has no parent directory",
+            dict_file_path.display()
+        )))?;
+        let mut temp_file = tempfile::Builder::new()
+            .prefix(".obs-dict-sync-")
+            .suffix(".tmp")
+            .tempfile_in(dict_dir)?;
+        {
+            let mut writer = BufWriter::new(temp_file.as_file_mut());
+            writer.write_all(&content)?;
+            writer.flush()?;
         }
-        // Checksum line is last line, should not have a newline
-        file.write_all(checksum_line.as_bytes())?;
-        // TODO: moar/better error handling
-        Ok(())
+        temp_file.as_file().sync_all()?;
+
+        // Temp files are created owner read/write only; the dictionary should keep whatever it had
+        let permissions = fs::metadata(&dict_file_path)?.permissions();
+        fs::set_permissions(temp_file.path(), permissions)?;
+
+        temp_file
+            .persist(&dict_file_path)
+            .map_err(|err| err.error)?;
+
+        // Make sure the rename itself survives a crash
+        #[cfg(unix)]
+        File::open(dict_dir)?.sync_all()?;
+
+        format.after_write(&dict_file_path)
     }
 }
 
@@ -193,72 +379,133 @@ fn canonicalize_or_create(path: &PathBuf) -> io::Result<PathBuf> {
     }
 }
 
-/// Returns all unique words in a given file
-fn get_words_from_file(dict_file: &PathBuf) -> Result<HashSet<String>, io::Error> {
+/// Returns all unique words in a given file along with everything else the format records about them
+fn get_words_from_file(
+    dict_file: &PathBuf,
+    options: &FormatOptions,
+) -> Result<(HashSet<String>, Metadata, ValidationReport), Error> {
     if !dict_file.exists() {
-        return Err(io::Error::new(
+        return Err(Error::Io(io::Error::new(
             io::ErrorKind::NotFound,
-            format!("File '{}' not found!", dict_file.to_str().unwrap()),
-        ));
+            format!("File '{}' not found!", dict_file.display()),
+        )));
     }
 
     //TODO: file type is text / contains words?
-    debug!(
-        "Reading dictionary file '{}'...",
-        dict_file.to_str().unwrap()
-    );
+    debug!("Reading dictionary file '{}'...", dict_file.display());
 
     // Let caller deal with file not found
-    let content = fs::read_to_string(dict_file)?;
-
-    // Assuming we read the file, we care about every line other than the hash line.
-    // A valid dictionary will have the file hash on the last line but we want to tolerate
-    // dictionary files that might have the hash line anywhere.
-    let dictionary_words: Vec<String> = content
-        .lines()
-        .map(String::from)
-        .filter(|line| !line.contains(CHECKSUM_PRELUDE))
-        .collect();
+    let bytes = fs::read(dict_file)?;
+    // One bad line shouldn't cost every word in the file, as long as the lines stand on their own
+    let (bytes, undecodable) = if options.format.has_word_per_line() {
+        validation::split_undecodable(&bytes)
+    } else {
+        (Cow::Borrowed(bytes.as_slice()), Vec::new())
+    };
+    if !undecodable.is_empty() && options.encoding_errors == EncodingErrorPolicy::Strict {
+        let lines: Vec<String> = undecodable.iter().map(ToString::to_string).collect();
+        return Err(Error::Encoding {
+            path: dict_file.clone(),
+            source: io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("not valid UTF-8 at {}", lines.join(", ")),
+            ),
+        });
+    }
+    for line in &undecodable {
+        warn!(
+            "'{}' {} is not valid UTF-8; {}",
+            dict_file.display(),
+            line,
+            match options.encoding_errors {
+                EncodingErrorPolicy::Quarantine => "it will be moved to the quarantine file",
+                _ => "it is left out and will be gone once the file is rewritten",
+            }
+        );
+    }
+    let parsed = options
+        .dictionary_format()
+        .parse(dict_file, &bytes)
+        .map_err(|err| match err {
+            FormatError::Encoding(message) => Error::Encoding {
+                path: dict_file.clone(),
+                source: io::Error::new(io::ErrorKind::InvalidData, message),
+            },
+            // A file that could be read but is not valid in its format
+            FormatError::Invalid(message) => Error::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("'{}': {}", dict_file.display(), message),
+            )),
+        })?;
+
+    let (words, mut report) =
+        validation::validate(parsed.words, options.invalid_words).map_err(|entries| {
+            Error::InvalidEntries {
+                path: dict_file.clone(),
+                entries,
+            }
+        })?;
+    report.undecodable = undecodable;
+    if !report.is_clean() {
+        warn!(
+            "'{}' has entries that are not valid words: {}",
+            dict_file.display(),
+            report
+        );
+        for entry in &report.trimmed {
+            info!("  trimmed {:?}", entry);
+        }
+        for (entry, problem) in &report.dropped {
+            info!("  dropped {:?} ({})", entry, problem);
+        }
+        for (entry, problem) in &report.kept {
+            info!("  kept {:?} ({})", entry, problem);
+        }
+    }
 
     debug!(
-        "After filtering '{}', have a total of {} words...",
-        dict_file.to_str().unwrap(),
-        dictionary_words.len()
+        "After filtering '{}', have a total of {} words and checksum is {}...",
+        dict_file.display(),
+        words.len(),
+        parsed.metadata.checksum
     );
 
-    Ok(HashSet::from_iter(dictionary_words))
+    Ok((words, parsed.metadata, report))
 }
 
-pub fn get_hydrated_path_from_str(dict_file: &str) -> Result<PathBuf, io::Error> {
+/// Side file next to a dictionary that lines which are not valid UTF-8 are moved to
+fn quarantine_path(dict_file: &Path) -> PathBuf {
+    let mut name = dict_file.file_name().unwrap_or_default().to_os_string();
+    name.push(".quarantine");
+    dict_file.with_file_name(name)
+}
+
+pub fn get_hydrated_path_from_str(dict_file: &str) -> Result<PathBuf, Error> {
     debug!("Resolving '{}'...", dict_file);
 
-    // TODO: don't swallow errors around missing env-vars or whatever else causes full() to fail
     // See: https://docs.rs/shellexpand/latest/shellexpand/fn.full_with_context.html
-    let dict_file = shellexpand::full(dict_file).unwrap().to_string();
+    let expanded = shellexpand::full(dict_file).map_err(|err| Error::PathExpansion {
+        path: dict_file.to_string(),
+        message: err.to_string(),
+    })?;
 
-    debug!("Resolved {:?}", dict_file);
-    Ok(PathBuf::from(dict_file))
+    debug!("Resolved {:?}", expanded);
+    Ok(PathBuf::from(expanded.to_string()))
 }
 
 // Rather than overload, I think the way to do this is to implement a "hydrate" trait for PathBuf and str?
-pub fn get_hydrated_path_from_pathbuf(dict_file: &PathBuf) -> Result<PathBuf, io::Error> {
-    debug!("Resolving '{:?}'...", dict_file);
-
+pub fn get_hydrated_path_from_pathbuf(dict_file: &Path) -> Result<PathBuf, Error> {
     // Convert PathBuf to string for shellexpand
-    let dict_file_str = dict_file.to_str().ok_or(io::Error::new(
-        io::ErrorKind::Other,
-        "Failed to convert PathBuf to str",
-    ))?;
-
-    // TODO: don't swallow errors around missing env-vars or whatever else causes full() to fail
-    // See: https://docs.rs/shellexpand/latest/shellexpand/fn.full_with_context.html
-    let dict_file = shellexpand::full(dict_file_str).unwrap().to_string();
+    let dict_file_str = dict_file.to_str().ok_or(Error::PathExpansion {
+        path: dict_file.to_string_lossy().to_string(),
+        message: String::from("Failed to convert PathBuf to str"),
+    })?;
 
-    debug!("Resolved {:?}", dict_file);
-    Ok(PathBuf::from(dict_file))
+    get_hydrated_path_from_str(dict_file_str)
 }
 
-fn calculate_hash_digest(words: &HashSet<String>) -> md5::Digest {
+/// Calculates the checksum that Chromium expects on the last line of a dictionary file
+pub fn calculate_hash_digest(words: &HashSet<String>) -> md5::Digest {
     // Python code to calc the hash properly:
     //     _words = "".join(sorted(words))
     //     checksum = md5(_words.encode()).hexdigest()
@@ -296,6 +543,8 @@ fn calculate_hash_digest(words: &HashSet<String>) -> md5::Digest {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::formats::chromium::CHECKSUM_PRELUDE;
+    use crate::formats::LineEnding;
     use std::io::Write;
     use std::path::Path;
     use tempfile::NamedTempFile;
@@ -323,6 +572,154 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_write_to_disk_replaces_contents() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("Custom Dictionary.txt");
+        fs::write(&file_path, "old\nwords\n")?;
+
+        let mut user_dict = UserDictionary::new(
+            file_path.clone(),
+            HashSet::from_iter(["beta".to_string(), "alpha".to_string()]),
+        );
+        user_dict.write_to_disk()?;
+
+        assert_eq!(
+            fs::read_to_string(&file_path)?,
+            format!("alpha\nbeta\n{}{:?}", CHECKSUM_PRELUDE, user_dict.hash)
+        );
+        // Nothing but the dictionary should be left behind
+        assert_eq!(fs::read_dir(temp_dir.path())?.count(), 1);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_to_disk_preserves_permissions() -> Result<(), Error> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("Custom Dictionary.txt");
+        fs::write(&file_path, "old\n")?;
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o640))?;
+
+        let mut user_dict = UserDictionary::new(file_path.clone(), HashSet::new());
+        user_dict.write_to_disk()?;
+
+        let mode = fs::metadata(&file_path)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_keeps_crlf_and_bom() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("Custom Dictionary.txt");
+        fs::write(&file_path, "\u{FEFF}beta\r\nalpha\r\n")?;
+
+        let mut user_dict = UserDictionary::new_from_pathbuf(file_path.clone())?;
+        // Neither the BOM nor the `\r` ends up in a word
+        assert_eq!(user_dict.get_sorted_words(), vec!["alpha", "beta"]);
+        user_dict.add_words(&["gamma".to_string()]);
+        user_dict.write_to_disk()?;
+
+        let written = fs::read_to_string(&file_path)?;
+        assert!(written.starts_with("\u{FEFF}alpha\r\nbeta\r\ngamma\r\nchecksum_v1 = "));
+        let read_back = UserDictionary::new_from_pathbuf(file_path.clone())?;
+        assert_eq!(read_back.checksum, Some(ChecksumStatus::Valid));
+
+        // The config can override what the file has
+        let options = FormatOptions {
+            line_ending: Some(LineEnding::Lf),
+            bom: Some(false),
+            ..FormatOptions::default()
+        };
+        let mut user_dict =
+            UserDictionary::new_from_pathbuf_with_options(file_path.clone(), &options)?;
+        user_dict.write_to_disk()?;
+        assert!(fs::read_to_string(&file_path)?.starts_with("alpha\nbeta\ngamma\nchecksum_v1 = "));
+        Ok(())
+    }
+
+    #[test]
+    fn test_written_dictionary_verifies() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("Custom Dictionary.txt");
+        let mut user_dict = UserDictionary::new(
+            file_path.clone(),
+            HashSet::from_iter(["zeta".to_string(), "Alpha".to_string()]),
+        );
+        user_dict.write_to_disk()?;
+
+        let read_back = UserDictionary::new_from_pathbuf(file_path)?;
+        assert_eq!(read_back.checksum, Some(ChecksumStatus::Valid));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hydrate_reports_missing_env_var() {
+        let err = get_hydrated_path_from_str("$OBS_DICT_SYNC_SURELY_NOT_SET/dict.txt").unwrap_err();
+        assert!(matches!(err, Error::PathExpansion { .. }));
+        assert_eq!(err.exit_code(), 5);
+    }
+
+    #[test]
+    fn test_encoding_error() -> Result<(), Error> {
+        let mut temp_file = NamedTempFile::new()?;
+        temp_file.write_all(b"caf\xe9\nword\n")?;
+
+        // The rest of the file is still read
+        let user_dict = UserDictionary::new_from_pathbuf(temp_file.path().to_path_buf())?;
+        assert_eq!(user_dict.get_sorted_words(), vec!["word"]);
+        assert_eq!(user_dict.validation.undecodable[0].line, 1);
+
+        let strict = FormatOptions {
+            encoding_errors: EncodingErrorPolicy::Strict,
+            ..FormatOptions::default()
+        };
+        let err =
+            UserDictionary::new_from_pathbuf_with_options(temp_file.path().to_path_buf(), &strict)
+                .unwrap_err();
+        assert!(matches!(err, Error::Encoding { .. }));
+        assert!(err.to_string().contains("line 1"), "{}", err);
+
+        // A format that is not one word per line can't skip a line, so the whole file fails even when skipping
+        let mut json_file = NamedTempFile::new()?;
+        json_file.write_all(b"{\"words\": [\"caf\xe9\", \"word\"]}")?;
+        let json = FormatOptions {
+            format: Format::Json,
+            ..FormatOptions::default()
+        };
+        assert_eq!(json.encoding_errors, EncodingErrorPolicy::Skip);
+        let err =
+            UserDictionary::new_from_pathbuf_with_options(json_file.path().to_path_buf(), &json)
+                .unwrap_err();
+        assert!(matches!(err, Error::Encoding { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quarantine_undecodable_lines() -> Result<(), Error> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("Custom Dictionary.txt");
+        fs::write(&file_path, b"caf\xe9\r\nword\n")?;
+        let options = FormatOptions {
+            encoding_errors: EncodingErrorPolicy::Quarantine,
+            ..FormatOptions::default()
+        };
+
+        let mut user_dict =
+            UserDictionary::new_from_pathbuf_with_options(file_path.clone(), &options)?;
+        user_dict.write_to_disk()?;
+        // Writing again doesn't quarantine the same lines twice
+        user_dict.write_to_disk()?;
+
+        let quarantine = temp_dir.path().join("Custom Dictionary.txt.quarantine");
+        assert_eq!(fs::read(quarantine)?, b"caf\xe9\n");
+        assert!(fs::read_to_string(&file_path)?.starts_with("word\nchecksum_v1 = "));
+        Ok(())
+    }
+
     #[test]
     fn test_error_on_invalid_path() {
         // Attempting to create a file in a non-existent directory within a temporary directory
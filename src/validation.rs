@@ -0,0 +1,288 @@
+//! Checks the entries read from a dictionary file before they take part in a merge.
+//!
+//! Chromium only accepts a word that is not empty and has no whitespace; anything else in a dictionary file is
+//! usually the remains of a bad merge or a hand edit and would otherwise be copied to every other dictionary.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::Deserialize;
+
+/// What to do with an entry that is not a valid word, set with `invalid_words = "..."`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InvalidWordPolicy {
+    /// Refuse to load the dictionary at all
+    Reject,
+    /// Strip leading and trailing whitespace; drop entries that are still not valid words
+    #[default]
+    Trim,
+    /// Drop every entry that is not a valid word as it is
+    Drop,
+    /// Keep every entry as it is; they are still reported
+    Keep,
+}
+
+/// What to do with lines of a dictionary file that are not valid UTF-8, set with `encoding_errors = "..."`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EncodingErrorPolicy {
+    /// Read the rest of the file and skip those lines; they are gone once the file is rewritten.
+    /// Only files with one word per line can skip a line; any other file that is not valid UTF-8 fails to load.
+    #[default]
+    Skip,
+    /// As `skip`, but the lines are moved to a `.quarantine` file next to the dictionary before it is rewritten
+    Quarantine,
+    /// Refuse to read the file and fail the run
+    Strict,
+}
+
+/// A line of a dictionary file that is not valid UTF-8
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndecodableLine {
+    /// Counted from 1
+    pub line: usize,
+    /// The line as it is in the file, without its line ending
+    pub bytes: Vec<u8>,
+}
+
+impl fmt::Display for UndecodableLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}: {:?}",
+            self.line,
+            String::from_utf8_lossy(&self.bytes)
+        )
+    }
+}
+
+/// Why an entry is not a valid word
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    /// Empty or nothing but whitespace
+    Blank,
+    /// Leading or trailing whitespace around an otherwise valid word
+    SurroundingWhitespace,
+    /// Whitespace inside the word
+    Whitespace,
+    /// A control character such as NUL or an escape left inside the word
+    Control,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Blank => write!(f, "blank"),
+            Problem::SurroundingWhitespace => write!(f, "leading or trailing whitespace"),
+            Problem::Whitespace => write!(f, "contains whitespace"),
+            Problem::Control => write!(f, "contains a control character"),
+        }
+    }
+}
+
+/// An entry as it was in the file and why it is not a valid word
+pub type InvalidEntry = (String, Problem);
+
+/// What the validation pass did to one file's entries
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Entries that had whitespace trimmed, as they were in the file
+    pub trimmed: Vec<String>,
+    /// Entries left out of the dictionary
+    pub dropped: Vec<InvalidEntry>,
+    /// Invalid entries kept as they were because the policy is `keep`
+    pub kept: Vec<InvalidEntry>,
+    /// Lines left out because they are not valid UTF-8
+    pub undecodable: Vec<UndecodableLine>,
+}
+
+impl ValidationReport {
+    /// True if every entry was a valid word
+    pub fn is_clean(&self) -> bool {
+        self.invalid_count() == 0
+    }
+
+    /// How many entries were not valid words
+    pub fn invalid_count(&self) -> usize {
+        self.trimmed.len() + self.dropped.len() + self.kept.len() + self.undecodable.len()
+    }
+
+    /// True if the words differ from the entries in the file, so writing the file would change it
+    pub fn changed_words(&self) -> bool {
+        !self.trimmed.is_empty() || !self.dropped.is_empty() || !self.undecodable.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "'{}' trimmed, '{}' dropped, '{}' kept as is, '{}' not valid UTF-8",
+            self.trimmed.len(),
+            self.dropped.len(),
+            self.kept.len(),
+            self.undecodable.len()
+        )
+    }
+}
+
+/// The problem with `entry`, or None if it is a valid word
+pub fn problem(entry: &str) -> Option<Problem> {
+    let trimmed = entry.trim();
+    if trimmed.is_empty() {
+        Some(Problem::Blank)
+    } else if trimmed.contains(char::is_control) {
+        Some(Problem::Control)
+    } else if trimmed.contains(char::is_whitespace) {
+        Some(Problem::Whitespace)
+    } else if trimmed.len() != entry.len() {
+        Some(Problem::SurroundingWhitespace)
+    } else {
+        None
+    }
+}
+
+/// Takes the lines that are not valid UTF-8 out of the content of a file that has one word per line
+pub fn split_undecodable(bytes: &[u8]) -> (Cow<'_, [u8]>, Vec<UndecodableLine>) {
+    if std::str::from_utf8(bytes).is_ok() {
+        return (Cow::Borrowed(bytes), Vec::new());
+    }
+    let mut decodable = Vec::with_capacity(bytes.len());
+    let mut undecodable = Vec::new();
+    for (i, line) in bytes.split_inclusive(|b| *b == b'\n').enumerate() {
+        if std::str::from_utf8(line).is_ok() {
+            decodable.extend_from_slice(line);
+            continue;
+        }
+        let content = line.strip_suffix(b"\n").unwrap_or(line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        undecodable.push(UndecodableLine {
+            line: i + 1,
+            bytes: content.to_vec(),
+        });
+    }
+    (Cow::Owned(decodable), undecodable)
+}
+
+/// Applies `policy` to every entry read from a file.
+/// Returns the words to use and what was changed, or with `reject`, the invalid entries.
+pub fn validate(
+    entries: HashSet<String>,
+    policy: InvalidWordPolicy,
+) -> Result<(HashSet<String>, ValidationReport), Vec<InvalidEntry>> {
+    let mut words = HashSet::with_capacity(entries.len());
+    let mut report = ValidationReport::default();
+    let mut rejected = Vec::new();
+    for entry in entries {
+        let Some(problem) = problem(&entry) else {
+            words.insert(entry);
+            continue;
+        };
+        match policy {
+            InvalidWordPolicy::Reject => rejected.push((entry, problem)),
+            InvalidWordPolicy::Keep => {
+                report.kept.push((entry.clone(), problem));
+                words.insert(entry);
+            }
+            InvalidWordPolicy::Trim if problem == Problem::SurroundingWhitespace => {
+                words.insert(entry.trim().to_string());
+                report.trimmed.push(entry);
+            }
+            InvalidWordPolicy::Trim | InvalidWordPolicy::Drop => {
+                report.dropped.push((entry, problem))
+            }
+        }
+    }
+    if !rejected.is_empty() {
+        rejected.sort_by(|a, b| a.0.cmp(&b.0));
+        return Err(rejected);
+    }
+    report.trimmed.sort();
+    report.dropped.sort_by(|a, b| a.0.cmp(&b.0));
+    report.kept.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok((words, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> HashSet<String> {
+        ["word", " padded\t", "", "two words", "bad\u{0}merge"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_problem() {
+        assert_eq!(problem("Obsidian"), None);
+        assert_eq!(problem("café"), None);
+        assert_eq!(problem("  "), Some(Problem::Blank));
+        assert_eq!(problem("wo\u{7}rd"), Some(Problem::Control));
+        // Trimming takes care of a stray `\r` at the end
+        assert_eq!(problem("word\r"), Some(Problem::SurroundingWhitespace));
+        assert_eq!(problem(" word"), Some(Problem::SurroundingWhitespace));
+        assert_eq!(problem("two words"), Some(Problem::Whitespace));
+    }
+
+    #[test]
+    fn test_policies() {
+        let (words, report) = validate(entries(), InvalidWordPolicy::Trim).unwrap();
+        assert_eq!(
+            words,
+            HashSet::from(["word".to_string(), "padded".to_string()])
+        );
+        assert_eq!(report.trimmed, vec![" padded\t"]);
+        assert_eq!(
+            report.dropped,
+            vec![
+                (String::new(), Problem::Blank),
+                ("bad\u{0}merge".to_string(), Problem::Control),
+                ("two words".to_string(), Problem::Whitespace),
+            ]
+        );
+
+        let (words, report) = validate(entries(), InvalidWordPolicy::Drop).unwrap();
+        assert_eq!(words, HashSet::from(["word".to_string()]));
+        assert_eq!(report.dropped.len(), 4);
+
+        let (words, report) = validate(entries(), InvalidWordPolicy::Keep).unwrap();
+        assert_eq!(words, entries());
+        assert_eq!(report.kept.len(), 4);
+
+        let rejected = validate(entries(), InvalidWordPolicy::Reject).unwrap_err();
+        assert_eq!(rejected.len(), 4);
+        assert!(validate(
+            HashSet::from(["word".to_string()]),
+            InvalidWordPolicy::Reject
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_split_undecodable() {
+        let (decodable, undecodable) = split_undecodable(b"alpha\ncaf\xe9\r\nbeta\n\xff");
+        assert_eq!(decodable.as_ref(), b"alpha\nbeta\n");
+        assert_eq!(
+            undecodable,
+            vec![
+                UndecodableLine {
+                    line: 2,
+                    bytes: b"caf\xe9".to_vec()
+                },
+                UndecodableLine {
+                    line: 4,
+                    bytes: b"\xff".to_vec()
+                },
+            ]
+        );
+        assert_eq!(undecodable[0].to_string(), "line 2: \"caf\u{FFFD}\"");
+
+        let (decodable, undecodable) = split_undecodable(b"alpha\n");
+        assert!(matches!(decodable, Cow::Borrowed(_)));
+        assert!(undecodable.is_empty());
+    }
+}